@@ -1,20 +1,289 @@
 use chrono::{DateTime, Utc};
+use engine::{Direction, GameExchange, Spawn, Topology};
+use pwatrip_twenty48_state::LeaderboardEntry;
 use serde::{Deserialize, Serialize};
+/// Machine-readable code of an error response. Each code is sent with a
+/// single HTTP status.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    InvalidRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    Conflict,
+    RevisionMismatch,
+    PayloadTooLarge,
+    InvalidGame,
+    IllegalMove,
+    RateLimited,
+    Internal,
+}
+
+impl ErrorCode {
+    pub const ALL: [ErrorCode; 11] = [
+        ErrorCode::InvalidRequest,
+        ErrorCode::Unauthorized,
+        ErrorCode::Forbidden,
+        ErrorCode::NotFound,
+        ErrorCode::Conflict,
+        ErrorCode::RevisionMismatch,
+        ErrorCode::PayloadTooLarge,
+        ErrorCode::InvalidGame,
+        ErrorCode::IllegalMove,
+        ErrorCode::RateLimited,
+        ErrorCode::Internal,
+    ];
+}
+
+/// The body of every error response.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Error {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl Error {
+    pub fn new(code: ErrorCode, message: &str) -> Error {
+        Error {
+            code,
+            message: message.to_owned(),
+        }
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct GameIdList {
     pub ids: Vec<String>,
 }
 
+/// A page of the leaderboard of a game, with the entries around the
+/// requesting player if they were asked for.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Leaderboard {
+    pub entries: Vec<LeaderboardEntry>,
+    pub total: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rank: Option<u64>,
+    #[serde(rename = "aroundMe", default, skip_serializing_if = "Option::is_none")]
+    pub around_me: Option<Vec<LeaderboardEntry>>,
+    /// Offset of the first entry around the requesting player
+    #[serde(
+        rename = "aroundOffset",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub around_offset: Option<u64>,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Player {
     #[serde(rename = "displayName")]
     pub display_name: String,
     #[serde(rename = "lastChange")]
     pub last_change: DateTime<Utc>,
+    /// Secret for signing bearer tokens, only sent in the response which
+    /// issues it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+}
+
+/// Lifetime statistics of a player, including games which have expired.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PlayerStats {
+    #[serde(rename = "gamesPlayed")]
+    pub games_played: u64,
+    #[serde(rename = "gamesFinished")]
+    pub games_finished: u64,
+    #[serde(rename = "bestScore")]
+    pub best_score: u64,
+    #[serde(rename = "averageScore")]
+    pub average_score: f64,
+    #[serde(rename = "maxTile")]
+    pub max_tile: u64,
+    #[serde(rename = "totalMoves")]
+    pub total_moves: u64,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Game {
     pub seed: String,
     pub size: u64,
+    pub topology: Topology,
+    pub blockers: Vec<usize>,
+    pub competitive: bool,
+    /// Commitment to the seed of a competition, see `engine::seed_commitment`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commitment: Option<String>,
+    /// Nonce of the commitment, kept secret like the seed until it is revealed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    /// Time when a competition closes and its seed is revealed
+    #[serde(rename = "revealAt", default, skip_serializing_if = "Option::is_none")]
+    pub reveal_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub status: GameStatus,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameStatus {
+    Open,
+    Revealed,
+}
+
+impl Default for GameStatus {
+    fn default() -> Self {
+        GameStatus::Open
+    }
+}
+
+/// Parameters of a competitive game to be created by the server.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct NewCompetitiveGame {
+    pub size: u64,
+    #[serde(default)]
+    pub topology: Topology,
+    #[serde(default)]
+    pub blockers: Vec<usize>,
+    /// Number of blockers to place from the seed, instead of giving their
+    /// cells in `blockers`.
+    #[serde(rename = "blockerCount", default)]
+    pub blocker_count: usize,
+    /// When set, the game is a competition which closes at this time. Its
+    /// seed is committed to at creation and revealed once it closes.
+    #[serde(rename = "revealAt", default)]
+    pub reveal_at: Option<DateTime<Utc>>,
+}
+
+/// A competitive game as seen by players, without the seed.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CompetitiveGame {
+    pub id: String,
+    pub size: u64,
+    pub topology: Topology,
+    pub blockers: Vec<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commitment: Option<String>,
+    #[serde(rename = "revealAt", default, skip_serializing_if = "Option::is_none")]
+    pub reveal_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MoveBatch {
+    pub moves: Vec<Direction>,
+}
+
+/// The result of applying a batch of moves to a competitive game, with the
+/// tile spawned after each move and the resulting board.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MoveBatchResult {
+    pub spawns: Vec<Spawn>,
+    pub score: u64,
+    #[serde(rename = "gameOver")]
+    pub game_over: bool,
+    #[serde(rename = "moveCount")]
+    pub move_count: usize,
+    /// Row-major tile exponents, with 0 for empty cells and 255 for blockers.
+    pub layout: Vec<u8>,
+    /// For competitions, the signature of the spawns with `spawn_signature`
+    /// in the engine, which can be checked once the seed is revealed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+/// The board of a replayed game after the number of moves given by its
+/// index, with the outcome of the last of these moves.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ReplayFrame {
+    pub index: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub direction: Option<Direction>,
+    pub score: u64,
+    #[serde(rename = "scoreGained")]
+    pub score_gained: u64,
+    pub merges: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spawn: Option<Spawn>,
+    #[serde(rename = "gameOver")]
+    pub game_over: bool,
+    /// Row-major tile exponents, with 0 for empty cells and 255 for blockers.
+    pub layout: Vec<u8>,
+}
+
+/// A range of the frames of a replayed game, out of `total` frames including
+/// the initial board.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ReplayFrames {
+    pub frames: Vec<ReplayFrame>,
+    pub total: usize,
+}
+
+/// The moves of a watched player game from the index `from` on, and the state
+/// of the game at `revision`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GameUpdate {
+    pub revision: String,
+    pub from: usize,
+    pub moves: Vec<Direction>,
+    pub score: u64,
+    #[serde(rename = "gameOver")]
+    pub game_over: bool,
+    #[serde(rename = "moveCount")]
+    pub move_count: usize,
+}
+
+/// Moves played on a stored player game since the given revision.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MoveDelta {
+    pub revision: String,
+    pub moves: Vec<Direction>,
+}
+
+/// The state of a player game after a move delta, whose new revision is sent
+/// as the `ETag`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MoveDeltaResult {
+    pub score: u64,
+    #[serde(rename = "gameOver")]
+    pub game_over: bool,
+    #[serde(rename = "moveCount")]
+    pub move_count: usize,
+}
+
+/// Games queued by a client while offline, to be stored in one request.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SyncBatch {
+    pub games: Vec<GameExchange>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SyncStatus {
+    /// The game was stored.
+    Accepted,
+    /// The stored game is not an ancestor of the game, which is returned
+    /// with the stored copy for the client to resolve.
+    Conflict,
+    /// The game cannot be stored, for the reason in the error.
+    Rejected,
+}
+
+/// The result of storing one game of a sync batch.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SyncResult {
+    pub id: String,
+    pub status: SyncStatus,
+    /// Revision of the stored game, for accepted games and conflicts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<String>,
+    /// The stored copy of the game, for conflicts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub game: Option<GameExchange>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<Error>,
+}
+
+/// The results of a sync batch, in the order of its games.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SyncBatchResult {
+    pub results: Vec<SyncResult>,
 }
@@ -1,15 +1,24 @@
 mod comm;
+mod middleware;
+mod openapi;
 
 use chrono::{DateTime, Utc};
-use comm::Player;
-use engine::{Game, GameExchange};
+use comm::{ErrorCode, GameStatus, Player};
+use engine::{
+    Direction, Game, GameExchange, GhostRace, Replay, Snapshot, Spawn, TileRace, Topology,
+};
 use once_cell::sync::Lazy;
+use openapi::Body;
 use pwatrip_twenty48_state::{
-    CreateGameRequest, CreatePlayerGameRequest, CreatePlayerRequest, GetGameRequest,
-    GetLeaderboardRequest, GetPlayerGameRequest, GetPlayerGamesRequest, GetPlayerRequest, State,
-    StateSender, UpdatePlayerGameRequest, UpdatePlayerRequest,
+    AppendPlayerGameMovesRequest, ClaimPlayerRequest, CreateGameRequest, CreatePlayerGameRequest,
+    CreatePlayerRequest, GetGameRequest, GetGameResponse, GetGlobalLeaderboardRequest,
+    GetLeaderboardRequest, GetLeaderboardResponse, GetPlayerGameRequest, GetPlayerGamesRequest,
+    GetPlayerRequest, GetPlayerStatsRequest, LeaderboardEntry, RevealGameRequest, State,
+    StateSender, TakeRateLimitTokenRequest, UpdatePlayerGameRequest, UpdatePlayerRequest,
+    WatchPlayerGameRequest,
 };
 use route_recognizer::{Params, Router};
+use std::collections::HashMap;
 use wasmbus_rpc::{actor::prelude::*, Timestamp};
 use wasmcloud_interface_httpserver::{HttpRequest, HttpResponse, HttpServer, HttpServerReceiver};
 
@@ -19,147 +28,882 @@ struct Twenty48BackendActor {}
 
 const PLAYER_ID_KEY: &'static str = "player_id";
 const GAME_ID_KEY: &'static str = "game_id";
+const PERIOD_KEY: &'static str = "period";
+const METRIC_KEY: &'static str = "metric";
+const OPPONENT_ID_KEY: &'static str = "opponent_id";
+/// Leaderboard pages have 100 entries unless a smaller limit is given.
+const MAX_LEADERBOARD_LIMIT: u32 = 100;
+/// At most 50 entries before and after a player can be requested.
+const MAX_LEADERBOARD_AROUND: u32 = 50;
+/// Bearer tokens may not be valid for longer than a day.
+const MAX_TOKEN_LIFETIME_SECS: i64 = 24 * 60 * 60;
+/// Replays are sent in ranges of at most 500 frames.
+const MAX_REPLAY_FRAMES: usize = 500;
+/// Watches wait 25 seconds for a change. The HTTP server must give the actor
+/// longer than that to respond, see `make link-http`.
+const WATCH_TIMEOUT_MS: u64 = 25_000;
+/// Each call to the state provider waits up to 1.25 seconds of a watch, so
+/// that it returns within the default RPC timeout of 2 seconds of the host.
+const WATCH_CALL_TIMEOUT_MS: u64 = 1_250;
+/// Sync batches are limited to 50 games, each of which is replayed.
+const MAX_SYNC_GAMES: usize = 50;
 static GET_PLAYER_ROUTE: Lazy<String> = Lazy::new(|| "GET/players/:".to_owned() + PLAYER_ID_KEY);
 static UPDATE_PLAYER_ROUTE: Lazy<String> = Lazy::new(|| "PUT/players/:".to_owned() + PLAYER_ID_KEY);
+static CLAIM_PLAYER_ROUTE: Lazy<String> =
+    Lazy::new(|| "POST/players/:".to_owned() + PLAYER_ID_KEY + "/claim");
 static GET_PLAYER_GAME_ROUTE: Lazy<String> =
     Lazy::new(|| "GET/players/:".to_owned() + PLAYER_ID_KEY + "/games/:" + GAME_ID_KEY);
 static PUT_PLAYER_GAME_ROUTE: Lazy<String> =
     Lazy::new(|| "PUT/players/:".to_owned() + PLAYER_ID_KEY + "/games/:" + GAME_ID_KEY);
+static PATCH_PLAYER_GAME_ROUTE: Lazy<String> =
+    Lazy::new(|| "PATCH/players/:".to_owned() + PLAYER_ID_KEY + "/games/:" + GAME_ID_KEY);
 static GET_PLAYER_GAMES_ROUTE: Lazy<String> =
     Lazy::new(|| "GET/players/:".to_owned() + PLAYER_ID_KEY + "/games");
+static GET_PLAYER_STATS_ROUTE: Lazy<String> =
+    Lazy::new(|| "GET/players/:".to_owned() + PLAYER_ID_KEY + "/stats");
 static GET_PLAYER_GAME_LEADERBOARD_ROUTE: Lazy<String> = Lazy::new(|| {
     "GET/players/:".to_owned() + PLAYER_ID_KEY + "/games/:" + GAME_ID_KEY + "/leaderboard"
 });
+static GET_PLAYER_GAME_REPLAY_ROUTE: Lazy<String> = Lazy::new(|| {
+    "GET/players/:".to_owned() + PLAYER_ID_KEY + "/games/:" + GAME_ID_KEY + "/replay.t48"
+});
+static GET_PLAYER_GAME_FRAMES_ROUTE: Lazy<String> =
+    Lazy::new(|| "GET/players/:".to_owned() + PLAYER_ID_KEY + "/games/:" + GAME_ID_KEY + "/replay");
+static WATCH_PLAYER_GAME_ROUTE: Lazy<String> =
+    Lazy::new(|| "GET/players/:".to_owned() + PLAYER_ID_KEY + "/games/:" + GAME_ID_KEY + "/watch");
+static GET_PLAYER_GAME_RACE_ROUTE: Lazy<String> = Lazy::new(|| {
+    "GET/players/:".to_owned()
+        + PLAYER_ID_KEY
+        + "/games/:"
+        + GAME_ID_KEY
+        + "/race/:"
+        + OPPONENT_ID_KEY
+});
 static GET_GAME_ROUTE: Lazy<String> = Lazy::new(|| "GET/games/:".to_owned() + GAME_ID_KEY);
+static POST_PLAYER_GAMES_ROUTE: Lazy<String> =
+    Lazy::new(|| "POST/players/:".to_owned() + PLAYER_ID_KEY + "/games");
+static POST_PLAYER_GAME_MOVES_ROUTE: Lazy<String> =
+    Lazy::new(|| "POST/players/:".to_owned() + PLAYER_ID_KEY + "/games/:" + GAME_ID_KEY + "/moves");
+static GET_GLOBAL_LEADERBOARD_ROUTE: Lazy<String> =
+    Lazy::new(|| "GET/leaderboards/:".to_owned() + PERIOD_KEY + "/:" + METRIC_KEY);
+static SYNC_PLAYER_GAMES_ROUTE: Lazy<String> =
+    Lazy::new(|| "POST/players/:".to_owned() + PLAYER_ID_KEY + "/sync");
+static GET_OPENAPI_ROUTE: Lazy<String> = Lazy::new(|| "GET/openapi.json".to_owned());
 
 enum HttpResponseCodes {
-    //Ok = 200,
+    Ok = 200,
+    Created = 201,
+    NoContent = 204,
     BadRequest = 400,
+    Unauthorized = 401,
+    Forbidden = 403,
     NotFound = 404,
+    Conflict = 409,
+    PreconditionFailed = 412,
+    PayloadTooLarge = 413,
+    UnprocessableEntity = 422,
+    TooManyRequests = 429,
     InternalServerError = 500,
 }
 
+/// The status sent with an error code.
+fn error_status(code: ErrorCode) -> u16 {
+    let status = match code {
+        ErrorCode::InvalidRequest => HttpResponseCodes::BadRequest,
+        ErrorCode::Unauthorized => HttpResponseCodes::Unauthorized,
+        ErrorCode::Forbidden => HttpResponseCodes::Forbidden,
+        ErrorCode::NotFound => HttpResponseCodes::NotFound,
+        ErrorCode::Conflict => HttpResponseCodes::Conflict,
+        ErrorCode::RevisionMismatch => HttpResponseCodes::PreconditionFailed,
+        ErrorCode::PayloadTooLarge => HttpResponseCodes::PayloadTooLarge,
+        ErrorCode::InvalidGame | ErrorCode::IllegalMove => HttpResponseCodes::UnprocessableEntity,
+        ErrorCode::RateLimited => HttpResponseCodes::TooManyRequests,
+        ErrorCode::Internal => HttpResponseCodes::InternalServerError,
+    };
+    status as u16
+}
+
+/// Builds an error response with a JSON body of the form
+/// `{"code": "not_found", "message": "..."}`.
+fn error_response(code: ErrorCode, message: &str) -> HttpResponse {
+    let error = comm::Error {
+        code,
+        message: message.to_owned(),
+    };
+    let mut header = HashMap::new();
+    header.insert(
+        "Content-Type".to_owned(),
+        vec!["application/json".to_owned()],
+    );
+    HttpResponse {
+        status_code: error_status(code),
+        header,
+        body: serde_json::to_string(&error).unwrap().as_bytes().to_vec(),
+    }
+}
+
+/// The details of RPC errors are logged, and not sent to the client.
 fn rpc_error_to_http_response(err: RpcError) -> Result<HttpResponse, RpcError> {
-    Ok(HttpResponse {
-        status_code: HttpResponseCodes::InternalServerError as u16,
-        body: format!("{:?}", err).as_bytes().to_vec(),
-        ..Default::default()
+    console_log(&format!("{:?}", err));
+    Ok(error_response(ErrorCode::Internal, "Internal server error"))
+}
+
+/// Converts a successful get game response to the stored game definition.
+/// Games stored before topologies and blockers were introduced are classic
+/// and have no blockers.
+fn game_from_response(resp: GetGameResponse) -> Result<comm::Game, RpcError> {
+    let topology = match resp.topology {
+        Some(t) => t
+            .parse()
+            .map_err(|_| RpcError::Other(format!("Invalid topology {}", t)))?,
+        None => Topology::Classic,
+    };
+    let blockers = match resp.blockers {
+        Some(b) => serde_json::from_str(&b)
+            .map_err(|_| RpcError::Other(format!("Invalid blockers {}", b)))?,
+        None => vec![],
+    };
+    let reveal_at = match resp.reveal_at {
+        Some(t) => Some(
+            DateTime::<Utc>::try_from(t)
+                .map_err(|_| RpcError::Other("Invalid reveal time".to_owned()))?,
+        ),
+        None => None,
+    };
+    Ok(comm::Game {
+        seed: resp.seed.unwrap().to_string(),
+        size: resp.size.unwrap(),
+        topology,
+        blockers,
+        competitive: resp.competitive.unwrap_or(false),
+        commitment: resp.commitment,
+        nonce: resp.nonce,
+        reveal_at,
+        status: match resp.status.as_deref() {
+            Some("Revealed") => GameStatus::Revealed,
+            _ => GameStatus::Open,
+        },
     })
 }
 
+/// Reveals the seed of a competition once it has closed. The status is
+/// updated lazily, by the first request which sees the closed competition.
+async fn reveal_if_due<S: State + Sync>(
+    ctx: &Context,
+    state: &S,
+    game_id: &str,
+    game: &mut comm::Game,
+) -> std::result::Result<(), RpcError> {
+    if game.status == GameStatus::Revealed || game.reveal_at.is_none_or(|t| t > Utc::now()) {
+        return Ok(());
+    }
+
+    let request = RevealGameRequest {
+        game_id: game_id.to_owned(),
+    };
+
+    if state.reveal_game(ctx, &request).await?.success {
+        game.status = GameStatus::Revealed;
+        return Ok(());
+    }
+
+    // The reveal fails if another request revealed the seed first, or if the
+    // clock of the database has not reached the reveal time yet, so the
+    // status is read back
+    let request = GetGameRequest {
+        game_id: game_id.to_owned(),
+    };
+    let resp = state.get_game(ctx, &request).await?;
+    if !resp.success {
+        return Err(RpcError::Other(resp.message.unwrap_or_default()));
+    }
+    game.status = game_from_response(resp)?.status;
+    Ok(())
+}
+
+/// Converts the failure of a state operation. Apart from duplicate keys, the
+/// state provider only fails to find things.
 fn failure_to_http_response(message: &str) -> Result<HttpResponse, RpcError> {
-    Ok(HttpResponse {
-        status_code: HttpResponseCodes::NotFound as u16,
-        body: message.as_bytes().to_vec(),
-        ..Default::default()
-    })
+    match message {
+        "Duplicate key" => Ok(error_response(ErrorCode::Conflict, message)),
+        _ => Ok(error_response(ErrorCode::NotFound, message)),
+    }
 }
 
-async fn handle_get_player_game(
+fn unauthorized_response(message: &str) -> HttpResponse {
+    let mut resp = error_response(ErrorCode::Unauthorized, message);
+    resp.header
+        .insert("WWW-Authenticate".to_owned(), vec!["Bearer".to_owned()]);
+    resp
+}
+
+/// Entity tag of a stored player game, which changes with every update.
+fn etag(revision: &str) -> String {
+    format!("\"{}\"", revision)
+}
+
+/// Checks an `If-Match` header against the revision of the stored player
+/// game, if there is one. Weak tags never match, as `If-Match` uses the strong
+/// comparison.
+fn if_match(value: &str, revision: Option<&str>) -> bool {
+    match revision {
+        Some(revision) => value
+            .split(',')
+            .map(|tag| tag.trim())
+            .any(|tag| tag == "*" || tag == etag(revision)),
+        None => false,
+    }
+}
+
+/// Builds a response with a stored player game, tagged with its revision.
+fn player_game_response(status: u16, body: Vec<u8>, revision: &str) -> HttpResponse {
+    let mut header = HashMap::new();
+    header.insert("ETag".to_owned(), vec![etag(revision)]);
+    HttpResponse {
+        status_code: status,
+        header,
+        body,
+    }
+}
+
+fn header<'a>(req: &'a HttpRequest, name: &str) -> Option<&'a str> {
+    req.header
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(name))
+        .and_then(|(_, v)| v.first())
+        .map(|v| v.as_str())
+}
+
+/// The address of the client. The HTTP server does not provide the address
+/// of the peer, so it is taken from the last entry of `X-Forwarded-For`,
+/// which is added by the reverse proxy in front of the HTTP server. Earlier
+/// entries are set by the client and cannot be trusted. Requests without the
+/// header share the address `unknown`, so that they are limited together
+/// rather than not at all.
+fn remote_address(req: &HttpRequest) -> &str {
+    header(req, "X-Forwarded-For")
+        .and_then(|v| v.rsplit(',').next())
+        .map(|a| a.trim())
+        .filter(|a| !a.is_empty())
+        .unwrap_or("unknown")
+}
+
+/// Token bucket limit of a route. Clients can make `capacity` requests in a
+/// burst, after which the bucket is refilled by `refill_per_minute` requests
+/// per minute. These are defaults, which the limits of the route in the
+/// `route_rate_limits` table of the state provider replace.
+struct RateLimit {
+    name: &'static str,
+    capacity: u32,
+    refill_per_minute: u32,
+}
+
+/// Takes a token from the bucket of the route for `key`, such as
+/// `address:<remote address>`. If the bucket is empty, the inner error is
+/// the response to send to the client.
+async fn check_rate_limit(
+    ctx: &Context,
+    limit: &RateLimit,
+    key: &str,
+) -> std::result::Result<std::result::Result<(), HttpResponse>, RpcError> {
+    let request = TakeRateLimitTokenRequest {
+        bucket: format!("{}:{}", limit.name, key),
+        capacity: limit.capacity,
+        refill_per_minute: limit.refill_per_minute,
+        route: limit.name.to_owned(),
+    };
+
+    let resp = StateSender::new()
+        .take_rate_limit_token(ctx, &request)
+        .await?;
+    if !resp.success {
+        let mut limited = error_response(ErrorCode::RateLimited, "Too many requests");
+        limited.header.insert(
+            "Retry-After".to_owned(),
+            vec![resp.retry_after.unwrap_or(1).to_string()],
+        );
+        return Ok(Err(limited));
+    }
+
+    Ok(Ok(()))
+}
+
+/// Generates the given number of random 32 bit words, hex encoded.
+async fn random_hex(words: usize) -> std::result::Result<String, RpcError> {
+    let mut hex = String::new();
+    for _ in 0..words {
+        hex += &format!("{:08x}", wasmcloud_interface_numbergen::random_32().await?);
+    }
+    Ok(hex)
+}
+
+/// Generates a 256 bit secret for a player, hex encoded.
+async fn new_secret() -> std::result::Result<String, RpcError> {
+    random_hex(8).await
+}
+
+/// Checks a token created with `engine::player_token`. The comparison of the
+/// signatures takes the same time wherever they differ.
+fn verify_token(secret: &str, player_id: &str, token: &str) -> bool {
+    let expires = match token
+        .split_once('.')
+        .and_then(|(e, _)| e.parse::<u64>().ok())
+    {
+        Some(expires) => expires,
+        None => return false,
+    };
+    let now = Utc::now().timestamp();
+    if expires as i64 <= now || expires as i64 > now + MAX_TOKEN_LIFETIME_SECS {
+        return false;
+    }
+    let expected = engine::player_token(secret, player_id, expires);
+    expected.len() == token.len()
+        && expected
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// The player a write request was authenticated for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Caller {
+    /// The request carries a valid bearer token for the player.
+    Verified,
+    /// The player was created before secrets existed and has not been
+    /// claimed, so there is no secret to check against.
+    Unclaimed,
+    /// The player does not exist.
+    Unknown,
+}
+
+/// Authenticates a write request for the player in the route. Requests for
+/// players with a secret are rejected unless they carry a valid bearer token.
+async fn authenticate(
     ctx: &Context,
+    req: &HttpRequest,
     params: &Params,
-) -> std::result::Result<HttpResponse, RpcError> {
-    let game_id = params.find(GAME_ID_KEY).unwrap();
+) -> std::result::Result<std::result::Result<Caller, HttpResponse>, RpcError> {
     let player_id = params.find(PLAYER_ID_KEY).unwrap();
 
     let sender = StateSender::new();
 
+    let request = GetPlayerRequest {
+        player_id: player_id.to_owned(),
+    };
+
+    let secret = match sender.get_player(ctx, &request).await? {
+        resp if resp.success => match resp.secret {
+            Some(secret) => secret,
+            None => return Ok(Ok(Caller::Unclaimed)),
+        },
+        _ => return Ok(Ok(Caller::Unknown)),
+    };
+
+    let token = header(req, "Authorization").and_then(|v| v.strip_prefix("Bearer "));
+
+    match token {
+        Some(token) if verify_token(&secret, player_id, token) => Ok(Ok(Caller::Verified)),
+        _ => Ok(Err(unauthorized_response(
+            "Invalid or missing bearer token",
+        ))),
+    }
+}
+
+/// A stored player game with its revision.
+struct LoadedGame {
+    gx: GameExchange,
+    revision: String,
+    /// Set for competitive games whose seed has not been revealed. The seed
+    /// in `gx` is only for use on the server and must not be sent.
+    seed_secret: bool,
+}
+
+impl LoadedGame {
+    /// The game as sent to clients. Without the seed, the board is described
+    /// by the spawned tiles, starting with the two starting tiles.
+    fn to_json(&self) -> Vec<u8> {
+        let mut value = serde_json::to_value(&self.gx).unwrap();
+        if self.seed_secret {
+            let spawns = Game::try_from(&self.gx).map_or(vec![], |g| g.spawns());
+            let object = value.as_object_mut().unwrap();
+            object.remove("seed");
+            object.insert("spawns".to_owned(), serde_json::to_value(spawns).unwrap());
+        }
+        serde_json::to_string(&value).unwrap().as_bytes().to_vec()
+    }
+}
+
+/// Loads a stored player game and its revision. If the game cannot be loaded,
+/// the inner error is the response to send to the client.
+async fn load_player_game<S: State + Sync>(
+    ctx: &Context,
+    state: &S,
+    game_id: &str,
+    player_id: &str,
+) -> std::result::Result<std::result::Result<LoadedGame, HttpResponse>, RpcError> {
     let request = GetPlayerGameRequest {
         game_id: game_id.to_owned(),
         player_id: player_id.to_owned(),
     };
 
-    let (moves, score) = match sender.get_player_game(ctx, &request).await {
+    let (moves, score, revision) = match state.get_player_game(ctx, &request).await {
         Ok(resp) => match resp.success {
-            true => (resp.moves.unwrap(), resp.score.unwrap() as usize),
-            false => return failure_to_http_response(&resp.message.unwrap()),
+            true => (
+                resp.moves.unwrap(),
+                resp.score.unwrap(),
+                resp.revision.unwrap(),
+            ),
+            false => return failure_to_http_response(&resp.message.unwrap()).map(Err),
         },
-        Err(err) => return rpc_error_to_http_response(err),
+        Err(err) => return rpc_error_to_http_response(err).map(Err),
     };
 
     let request = GetGameRequest {
         game_id: game_id.to_owned(),
     };
 
-    let (seed, size) = match sender.get_game(ctx, &request).await {
+    let mut game = match state.get_game(ctx, &request).await {
         Ok(resp) => match resp.success {
-            true => (resp.seed.unwrap().to_string(), resp.size.unwrap() as usize),
-            false => return failure_to_http_response(&resp.message.unwrap()),
+            true => match game_from_response(resp) {
+                Ok(game) => game,
+                Err(err) => return rpc_error_to_http_response(err).map(Err),
+            },
+            false => return failure_to_http_response(&resp.message.unwrap()).map(Err),
         },
-        Err(err) => return rpc_error_to_http_response(err),
+        Err(err) => return rpc_error_to_http_response(err).map(Err),
     };
 
-    let gx = match GameExchange::new(String::new(), game_id.to_owned(), score, seed, size, &moves) {
-        Ok(gx) => gx,
-        Err(_) => {
-            return Ok(HttpResponse {
-                status_code: HttpResponseCodes::InternalServerError as u16,
-                body: "Error creating game exchange response".as_bytes().to_vec(),
-                ..Default::default()
-            })
+    if let Err(err) = reveal_if_due(ctx, state, game_id, &mut game).await {
+        return rpc_error_to_http_response(err).map(Err);
+    }
+
+    match GameExchange::new(
+        String::new(),
+        game_id.to_owned(),
+        score,
+        game.seed,
+        game.size as usize,
+        &moves,
+    ) {
+        Ok(mut gx) => {
+            gx.set_topology(game.topology);
+            gx.set_blockers(game.blockers);
+            if game.reveal_at.is_some() {
+                // The games of a competition are hidden until its seed is
+                // revealed, and are then verified against the commitment
+                if game.status != GameStatus::Revealed {
+                    return Ok(Err(error_response(
+                        ErrorCode::Forbidden,
+                        "Competition games are hidden until the seed is revealed",
+                    )));
+                }
+                let verified = Game::try_from(&gx).is_ok_and(|g| {
+                    let nonce = game.nonce.as_deref().unwrap_or_default();
+                    game.commitment == Some(engine::seed_commitment(game_id, g.get_seed(), nonce))
+                });
+                if !verified {
+                    return Ok(Err(error_response(
+                        ErrorCode::Internal,
+                        "Stored game does not match the seed commitment",
+                    )));
+                }
+                return Ok(Ok(LoadedGame {
+                    gx,
+                    revision,
+                    seed_secret: false,
+                }));
+            }
+            // The moves of a competitive game would let other players play
+            // ahead of it, so they are only available once the game is over
+            if game.competitive && !Game::try_from(&gx).is_ok_and(|g| g.get_game_over()) {
+                return Ok(Err(error_response(
+                    ErrorCode::Forbidden,
+                    "Competitive games are hidden until they are over",
+                )));
+            }
+            Ok(Ok(LoadedGame {
+                gx,
+                revision,
+                seed_secret: game.competitive && game.status != GameStatus::Revealed,
+            }))
+        }
+        Err(_) => Ok(Err(error_response(
+            ErrorCode::Internal,
+            "Error creating game exchange response",
+        ))),
+    }
+}
+
+async fn handle_get_player_game(
+    ctx: &Context,
+    params: &Params,
+) -> std::result::Result<HttpResponse, RpcError> {
+    let game_id = params.find(GAME_ID_KEY).unwrap();
+    let player_id = params.find(PLAYER_ID_KEY).unwrap();
+
+    let loaded = match load_player_game(ctx, &StateSender::new(), game_id, player_id).await? {
+        Ok(loaded) => loaded,
+        Err(resp) => return Ok(resp),
+    };
+
+    Ok(player_game_response(
+        HttpResponseCodes::Ok as u16,
+        loaded.to_json(),
+        &loaded.revision,
+    ))
+}
+
+async fn handle_get_player_game_replay(
+    ctx: &Context,
+    params: &Params,
+) -> std::result::Result<HttpResponse, RpcError> {
+    let game_id = params.find(GAME_ID_KEY).unwrap();
+    let player_id = params.find(PLAYER_ID_KEY).unwrap();
+
+    let loaded = match load_player_game(ctx, &StateSender::new(), game_id, player_id).await? {
+        Ok(loaded) => loaded,
+        Err(resp) => return Ok(resp),
+    };
+
+    // Replay files hold the seed
+    if loaded.seed_secret {
+        return Ok(error_response(
+            ErrorCode::Forbidden,
+            "Replay files of competitive games are hidden until the seed is revealed",
+        ));
+    }
+
+    let bytes = match Game::try_from(&loaded.gx).and_then(|game| Replay::new(game).to_bytes()) {
+        Ok(bytes) => bytes,
+        Err(message) => return Ok(error_response(ErrorCode::Internal, message)),
+    };
+
+    let mut header = HashMap::new();
+    header.insert(
+        "Content-Type".to_owned(),
+        vec!["application/octet-stream".to_owned()],
+    );
+    header.insert(
+        "Content-Disposition".to_owned(),
+        vec![format!("attachment; filename=\"{}.t48\"", game_id)],
+    );
+
+    Ok(HttpResponse {
+        header,
+        body: bytes,
+        ..Default::default()
+    })
+}
+
+/// Replays a game from its initial board, returning the frames from `from` up
+/// to but not including `to`.
+fn replay_frames(game: &Game, from: usize, to: usize) -> Vec<comm::ReplayFrame> {
+    let moves = game.get_moves();
+    let mut current = Game::new_from_definition(
+        game.get_size(),
+        game.get_seed(),
+        &game.get_id(),
+        game.get_topology(),
+        game.get_blockers(),
+    )
+    .unwrap();
+
+    let mut frames = vec![];
+    let mut played = None;
+    for index in 0..to.min(moves.len() + 1) {
+        if index > 0 {
+            let d = Direction::from_code(moves[index - 1]).unwrap();
+            let result = current.play(d.clone());
+            current = result.game().unwrap();
+            played = Some((d, result));
+        }
+        if index >= from {
+            frames.push(comm::ReplayFrame {
+                index,
+                direction: played.as_ref().map(|(d, _)| d.clone()),
+                score: current.get_score(),
+                score_gained: played.as_ref().map_or(0, |(_, r)| r.score_gained),
+                merges: played.as_ref().map_or(0, |(_, r)| r.merges),
+                spawn: played.as_ref().and_then(|(_, r)| r.spawn()),
+                game_over: current.get_game_over(),
+                layout: current.layout(),
+            });
+        }
+    }
+    frames
+}
+
+async fn handle_get_player_game_frames(
+    ctx: &Context,
+    params: &Params,
+    query_string: &str,
+) -> std::result::Result<HttpResponse, RpcError> {
+    let game_id = params.find(GAME_ID_KEY).unwrap();
+    let player_id = params.find(PLAYER_ID_KEY).unwrap();
+
+    get_player_game_frames(ctx, &StateSender::new(), game_id, player_id, query_string).await
+}
+
+/// Sends the boards of a stored player game, replayed from its seed and
+/// moves. Long games are fetched in ranges with the `from` and `to` query
+/// parameters, where frame `i` is the board after `i` moves and `to` is
+/// exclusive, so `from` may not be greater than the number of moves.
+async fn get_player_game_frames<S: State + Sync>(
+    ctx: &Context,
+    state: &S,
+    game_id: &str,
+    player_id: &str,
+    query_string: &str,
+) -> std::result::Result<HttpResponse, RpcError> {
+    let mut from: usize = 0;
+    let mut to = None;
+    for (key, value) in form_urlencoded::parse(query_string.as_bytes()) {
+        let target = match key.as_ref() {
+            "from" => &mut from,
+            "to" => to.get_or_insert(0),
+            _ => continue,
+        };
+        match value.parse() {
+            Ok(v) => *target = v,
+            Err(_) => {
+                return Ok(error_response(
+                    ErrorCode::InvalidRequest,
+                    &format!("Invalid value for {}", key),
+                ))
+            }
+        }
+    }
+    let to = match to {
+        Some(to) if to < from => {
+            return Ok(error_response(
+                ErrorCode::InvalidRequest,
+                "to is less than from",
+            ))
         }
+        Some(to) => to.min(from.saturating_add(MAX_REPLAY_FRAMES)),
+        None => from.saturating_add(MAX_REPLAY_FRAMES),
+    };
+
+    let loaded = match load_player_game(ctx, state, game_id, player_id).await? {
+        Ok(loaded) => loaded,
+        Err(resp) => return Ok(resp),
+    };
+
+    let game = match Game::try_from(&loaded.gx) {
+        Ok(game) => game,
+        Err(message) => return Ok(error_response(ErrorCode::Internal, message)),
+    };
+
+    if from > game.get_moves().len() {
+        return Ok(error_response(
+            ErrorCode::InvalidRequest,
+            "from is greater than the number of moves",
+        ));
+    }
+
+    let replay = comm::ReplayFrames {
+        frames: replay_frames(&game, from, to),
+        total: game.get_moves().len() + 1,
     };
 
     Ok(HttpResponse {
-        body: serde_json::to_string(&gx).unwrap().as_bytes().to_vec(),
+        body: serde_json::to_string(&replay).unwrap().as_bytes().to_vec(),
         ..Default::default()
     })
 }
 
-async fn handle_update_player_game(
+/// The moves of a game from the index `from` on, which is capped at the
+/// number of moves.
+fn game_update(game: &Game, revision: String, from: usize) -> comm::GameUpdate {
+    let moves = game.get_moves();
+    let from = from.min(moves.len());
+    comm::GameUpdate {
+        revision,
+        from,
+        moves: moves[from..]
+            .iter()
+            .filter_map(|code| Direction::from_code(*code))
+            .collect(),
+        score: game.get_score(),
+        game_over: game.get_game_over(),
+        move_count: moves.len(),
+    }
+}
+
+async fn handle_watch_player_game(
     ctx: &Context,
     params: &Params,
-    body: &Vec<u8>,
+    query_string: &str,
 ) -> std::result::Result<HttpResponse, RpcError> {
     let game_id = params.find(GAME_ID_KEY).unwrap();
     let player_id = params.find(PLAYER_ID_KEY).unwrap();
 
-    let body_str = match std::str::from_utf8(&body) {
-        Ok(s) => s,
-        Err(_) => {
-            return Ok(HttpResponse {
-                status_code: HttpResponseCodes::BadRequest as u16,
-                body: "Error parsing request body as string".as_bytes().to_vec(),
-                ..Default::default()
-            })
+    watch_player_game(ctx, &StateSender::new(), game_id, player_id, query_string).await
+}
+
+/// Long-polls a player game for spectators. With `since_revision`, waits until
+/// the stored game has another revision, and responds with `204` if it does
+/// not change in time. The moves before the index given with `from` are left
+/// out, so that spectators only receive the moves they have not seen, which
+/// is why `from` is required with `since_revision`.
+async fn watch_player_game<S: State + Sync>(
+    ctx: &Context,
+    state: &S,
+    game_id: &str,
+    player_id: &str,
+    query_string: &str,
+) -> std::result::Result<HttpResponse, RpcError> {
+    let mut since_revision = None;
+    let mut from = None;
+    for (key, value) in form_urlencoded::parse(query_string.as_bytes()) {
+        match key.as_ref() {
+            "since_revision" => since_revision = Some(value.into_owned()),
+            "from" => match value.parse() {
+                Ok(v) => from = Some(v),
+                Err(_) => {
+                    return Ok(error_response(
+                        ErrorCode::InvalidRequest,
+                        "Invalid value for from",
+                    ))
+                }
+            },
+            _ => (),
         }
+    }
+
+    if since_revision.is_some() && from.is_none() {
+        return Ok(error_response(
+            ErrorCode::InvalidRequest,
+            "from is required with since_revision",
+        ));
+    }
+
+    let mut loaded = match load_player_game(ctx, state, game_id, player_id).await? {
+        Ok(loaded) => loaded,
+        Err(resp) => return Ok(resp),
     };
 
-    let new_gx = match GameExchange::from_json(body_str.to_owned()) {
-        Some(gx) => gx,
-        None => {
+    if since_revision.as_ref() == Some(&loaded.revision) {
+        let request = WatchPlayerGameRequest {
+            game_id: game_id.to_owned(),
+            player_id: player_id.to_owned(),
+            revision: loaded.revision,
+            timeout_ms: WATCH_CALL_TIMEOUT_MS,
+        };
+
+        let mut changed = false;
+        for _ in 0..WATCH_TIMEOUT_MS / WATCH_CALL_TIMEOUT_MS {
+            match state.watch_player_game(ctx, &request).await {
+                Ok(resp) if resp.changed == Some(true) => {
+                    changed = true;
+                    break;
+                }
+                Ok(_) => (),
+                Err(err) => return rpc_error_to_http_response(err),
+            }
+        }
+
+        if !changed {
             return Ok(HttpResponse {
-                status_code: HttpResponseCodes::BadRequest as u16,
-                body: "Error parsing request body".as_bytes().to_vec(),
+                status_code: HttpResponseCodes::NoContent as u16,
                 ..Default::default()
-            })
+            });
         }
+
+        loaded = match load_player_game(ctx, state, game_id, player_id).await? {
+            Ok(loaded) => loaded,
+            Err(resp) => return Ok(resp),
+        };
+    }
+
+    let game = match Game::try_from(&loaded.gx) {
+        Ok(game) => game,
+        Err(message) => return Ok(error_response(ErrorCode::Internal, message)),
     };
 
-    if new_gx.get_size() != 4 {
-        return Ok(HttpResponse {
-            status_code: HttpResponseCodes::BadRequest as u16,
-            body: "Only games of size 4 are allowed".as_bytes().to_vec(),
+    let update = game_update(&game, loaded.revision.clone(), from.unwrap_or(0));
+    Ok(player_game_response(
+        HttpResponseCodes::Ok as u16,
+        serde_json::to_string(&update).unwrap().as_bytes().to_vec(),
+        &loaded.revision,
+    ))
+}
+
+/// Compares the game of a player with the game of an opponent on the same
+/// game ID, move by move, so that the opponent can be shown as a ghost.
+async fn handle_get_player_game_race(
+    ctx: &Context,
+    params: &Params,
+) -> std::result::Result<HttpResponse, RpcError> {
+    let game_id = params.find(GAME_ID_KEY).unwrap();
+
+    let sender = StateSender::new();
+
+    let mut games = vec![];
+    for player_id in [params.find(PLAYER_ID_KEY), params.find(OPPONENT_ID_KEY)] {
+        let loaded = match load_player_game(ctx, &sender, game_id, player_id.unwrap()).await? {
+            Ok(loaded) => loaded,
+            Err(resp) => return Ok(resp),
+        };
+        match Game::try_from(&loaded.gx) {
+            Ok(game) => games.push(game),
+            Err(message) => return Ok(error_response(ErrorCode::Internal, message)),
+        }
+    }
+
+    match GhostRace::new(&games[0], &games[1]) {
+        Ok(race) => Ok(HttpResponse {
+            body: serde_json::to_string(&race).unwrap().as_bytes().to_vec(),
             ..Default::default()
-        });
+        }),
+        Err(message) => Ok(error_response(ErrorCode::Internal, message)),
+    }
+}
+
+/// A player game stored by `store_player_game`, with its new revision.
+struct StoredGame {
+    gx: GameExchange,
+    revision: String,
+    created: bool,
+}
+
+/// Stores a player game, which must descend from the stored game if there is
+/// one. With `If-Match`, the game is only stored over the given revision, and
+/// concurrent updates fail instead of being retried. If the game cannot be
+/// stored, the inner error says why.
+async fn store_player_game(
+    ctx: &Context,
+    player_id: &str,
+    game_id: &str,
+    new_gx: GameExchange,
+    if_match_value: Option<&str>,
+) -> std::result::Result<std::result::Result<StoredGame, comm::Error>, RpcError> {
+    if new_gx.get_size() != 4 {
+        return Ok(Err(comm::Error::new(
+            ErrorCode::InvalidGame,
+            "Only games of size 4 are allowed",
+        )));
     }
 
     let new_game = match Game::try_from(&new_gx) {
         Ok(game) => game,
         Err(_) => {
-            return Ok(HttpResponse {
-                status_code: HttpResponseCodes::BadRequest as u16,
-                body: "Error parsing request body as game".as_bytes().to_vec(),
-                ..Default::default()
-            })
+            return Ok(Err(comm::Error::new(
+                ErrorCode::InvalidGame,
+                "Error parsing request body as game",
+            )))
         }
     };
 
     let sender = StateSender::new();
 
-    let (seed, size, game_existed) = {
+    let (seed, size, topology, blockers, competitive, game_existed) = {
         let seed;
         let size;
+        let topology;
+        let blockers;
+        let competitive;
         let game_existed;
 
         loop {
@@ -169,16 +913,22 @@ async fn handle_update_player_game(
 
             let game_resp = match sender.get_game(ctx, &request).await {
                 Ok(resp) => match resp.success {
-                    true => Some((resp.seed.unwrap().to_string(), resp.size.unwrap() as usize)),
+                    true => match game_from_response(resp) {
+                        Ok(game) => Some(game),
+                        Err(err) => return Err(err),
+                    },
                     false => None,
                 },
-                Err(err) => return rpc_error_to_http_response(err),
+                Err(err) => return Err(err),
             };
 
             match game_resp {
-                Some((sed, siz)) => {
-                    seed = sed;
-                    size = siz;
+                Some(game) => {
+                    seed = game.seed;
+                    size = game.size as usize;
+                    topology = game.topology;
+                    blockers = game.blockers;
+                    competitive = game.competitive;
                     game_existed = true;
                     break;
                 }
@@ -187,53 +937,52 @@ async fn handle_update_player_game(
                         game_id: game_id.to_owned(),
                         seed: new_game.get_seed(),
                         size: new_game.get_size() as u64,
+                        topology: Some(new_game.get_topology().to_string()),
+                        blockers: Some(serde_json::to_string(&new_game.get_blockers()).unwrap()),
+                        competitive: None,
+                        commitment: None,
+                        nonce: None,
+                        reveal_at: None,
                     };
                     match sender.create_game(ctx, &request).await {
                         Ok(resp) => match resp.success {
                             true => {
                                 seed = new_game.get_seed().to_string();
                                 size = new_game.get_size();
+                                topology = new_game.get_topology();
+                                blockers = new_game.get_blockers();
+                                competitive = false;
                                 game_existed = false;
                                 break;
                             }
                             false => (),
                         },
-                        Err(err) => return rpc_error_to_http_response(err),
+                        Err(err) => return Err(err),
                     }
                 }
             };
         }
 
-        (seed, size, game_existed)
+        (seed, size, topology, blockers, competitive, game_existed)
     };
 
-    loop {
-        let request = GetPlayerRequest {
-            player_id: player_id.to_owned(),
-        };
+    if competitive {
+        return Ok(Err(comm::Error::new(
+            ErrorCode::Conflict,
+            "Competitive games only accept moves through the moves route",
+        )));
+    }
 
-        match sender.get_player(ctx, &request).await {
-            Ok(resp) => match resp.success {
-                true => break,
-                false => {
-                    let request = CreatePlayerRequest {
-                        display_name: new_gx.get_player().to_owned(),
-                        last_change: Timestamp::from(Utc::now()),
-                        player_id: player_id.to_owned(),
-                    };
-
-                    match sender.create_player(ctx, &request).await {
-                        Ok(resp) => match resp.success {
-                            true => break,
-                            false => (),
-                        },
-                        Err(err) => return rpc_error_to_http_response(err),
-                    }
-                }
-            },
-            Err(err) => return rpc_error_to_http_response(err),
-        }
-    }
+    if seed != new_game.get_seed().to_string()
+        || size != new_game.get_size()
+        || topology != new_game.get_topology()
+        || blockers != new_game.get_blockers()
+    {
+        return Ok(Err(comm::Error::new(
+            ErrorCode::Conflict,
+            "Submitted game does not match the stored game parameters",
+        )));
+    }
 
     loop {
         let player_game_data = match game_existed {
@@ -247,17 +996,27 @@ async fn handle_update_player_game(
                     Ok(resp) => match resp.success {
                         true => Some((
                             resp.moves.unwrap(),
-                            resp.score.unwrap() as usize,
+                            resp.score.unwrap(),
                             resp.revision.unwrap(),
                         )),
                         false => None,
                     },
-                    Err(err) => return rpc_error_to_http_response(err),
+                    Err(err) => return Err(err),
                 }
             }
             false => None,
         };
 
+        if let Some(value) = if_match_value {
+            let revision = player_game_data.as_ref().map(|(_, _, r)| r.as_str());
+            if !if_match(value, revision) {
+                return Ok(Err(comm::Error::new(
+                    ErrorCode::RevisionMismatch,
+                    "Stored game does not match If-Match",
+                )));
+            }
+        }
+
         let old_game_option = match &player_game_data {
             Some((moves, score, _)) => {
                 match GameExchange::new(
@@ -268,10 +1027,14 @@ async fn handle_update_player_game(
                     size,
                     &moves,
                 ) {
-                    Ok(gx) => match Game::try_from(&gx) {
-                        Ok(game) => Some(game),
-                        Err(_) => None,
-                    },
+                    Ok(mut gx) => {
+                        gx.set_topology(topology);
+                        gx.set_blockers(blockers.clone());
+                        match Game::try_from(&gx) {
+                            Ok(game) => Some(game),
+                            Err(_) => None,
+                        }
+                    }
                     Err(_) => None,
                 }
             }
@@ -279,67 +1042,379 @@ async fn handle_update_player_game(
         };
 
         if let Some(old_game) = old_game_option {
+            let (_, _, revision) = player_game_data.unwrap();
+
             if old_game == new_game {
-                return Ok(HttpResponse {
-                    body: body.clone(),
-                    ..Default::default()
-                });
+                return Ok(Ok(StoredGame {
+                    gx: new_gx,
+                    revision,
+                    created: false,
+                }));
             }
 
             if !old_game.is_ancestor(&new_game) {
-                return Ok(HttpResponse {
-                    status_code: HttpResponseCodes::BadRequest as u16,
-                    body: "Submitted game is not a descendant of the stored game"
-                        .as_bytes()
-                        .to_vec(),
-                    ..Default::default()
-                });
+                return Ok(Err(comm::Error::new(
+                    ErrorCode::Conflict,
+                    "Submitted game is not a descendant of the stored game",
+                )));
             }
 
-            let (_, _, revision) = player_game_data.unwrap();
-
             let request = UpdatePlayerGameRequest {
                 game_id: game_id.to_owned(),
+                game_over: Some(new_game.get_game_over()),
+                max_tile: Some(new_game.max_tile()),
                 moves: new_gx.get_moves_str(),
                 player_id: player_id.to_owned(),
+                snapshot: Some(serde_json::to_string(&new_game.snapshot()).unwrap()),
                 revision,
-                score: new_game.get_score() as u64,
+                score: new_game.get_score(),
             };
 
             match sender.update_player_game(ctx, &request).await {
                 Ok(resp) => match resp.success {
                     true => {
-                        return Ok(HttpResponse {
-                            body: new_gx.to_json().unwrap().as_bytes().to_vec(),
-                            ..Default::default()
-                        })
+                        return Ok(Ok(StoredGame {
+                            gx: new_gx,
+                            revision: resp.revision.unwrap(),
+                            created: false,
+                        }))
+                    }
+                    // The game was updated concurrently, which only the
+                    // client can resolve if it asked for a revision
+                    false if if_match_value.is_some() => {
+                        return Ok(Err(comm::Error::new(
+                            ErrorCode::RevisionMismatch,
+                            "Stored game does not match If-Match",
+                        )))
                     }
                     false => (),
                 },
-                Err(err) => return rpc_error_to_http_response(err),
+                Err(err) => return Err(err),
             }
         } else {
             let request = CreatePlayerGameRequest {
                 game_id: game_id.to_owned(),
+                game_over: Some(new_game.get_game_over()),
+                max_tile: Some(new_game.max_tile()),
                 moves: new_gx.get_moves_str(),
                 player_id: player_id.to_owned(),
-                score: new_game.get_score() as u64,
+                snapshot: Some(serde_json::to_string(&new_game.snapshot()).unwrap()),
+                score: new_game.get_score(),
             };
 
             match sender.create_player_game(ctx, &request).await {
                 Ok(resp) => match resp.success {
                     true => {
-                        return Ok(HttpResponse {
-                            body: new_gx.to_json().unwrap().as_bytes().to_vec(),
-                            ..Default::default()
-                        })
+                        return Ok(Ok(StoredGame {
+                            gx: new_gx,
+                            revision: resp.revision.unwrap(),
+                            created: true,
+                        }))
                     }
                     false => (),
                 },
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+/// Stores a player game sent with `PUT`, responding with the game and its
+/// revision.
+async fn handle_update_player_game(
+    ctx: &Context,
+    params: &Params,
+    body: &Vec<u8>,
+    if_match_value: Option<&str>,
+) -> std::result::Result<HttpResponse, RpcError> {
+    let game_id = params.find(GAME_ID_KEY).unwrap();
+    let player_id = params.find(PLAYER_ID_KEY).unwrap();
+
+    let body_str = match std::str::from_utf8(&body) {
+        Ok(s) => s,
+        Err(_) => {
+            return Ok(error_response(
+                ErrorCode::InvalidRequest,
+                "Error parsing request body as string",
+            ))
+        }
+    };
+
+    let new_gx = match GameExchange::from_json(body_str.to_owned()) {
+        Some(gx) => gx,
+        None => {
+            return Ok(error_response(
+                ErrorCode::InvalidRequest,
+                "Error parsing request body",
+            ))
+        }
+    };
+
+    match store_player_game(ctx, player_id, game_id, new_gx, if_match_value).await {
+        Ok(Ok(stored)) => Ok(player_game_response(
+            match stored.created {
+                true => HttpResponseCodes::Created as u16,
+                false => HttpResponseCodes::Ok as u16,
+            },
+            stored.gx.to_json().unwrap().as_bytes().to_vec(),
+            &stored.revision,
+        )),
+        Ok(Err(error)) => Ok(error_response(error.code, &error.message)),
+        Err(err) => rpc_error_to_http_response(err),
+    }
+}
+
+async fn handle_append_player_game_moves(
+    ctx: &Context,
+    params: &Params,
+    body: &[u8],
+) -> std::result::Result<HttpResponse, RpcError> {
+    let game_id = params.find(GAME_ID_KEY).unwrap();
+    let player_id = params.find(PLAYER_ID_KEY).unwrap();
+
+    let delta = match serde_json::from_slice::<comm::MoveDelta>(body) {
+        Ok(delta) => delta,
+        Err(_) => {
+            return Ok(error_response(
+                ErrorCode::InvalidRequest,
+                "Error parsing request body",
+            ))
+        }
+    };
+
+    append_player_game_moves(ctx, &StateSender::new(), game_id, player_id, &delta).await
+}
+
+/// Appends moves to a stored player game, which must be at the revision given
+/// with them. Only the new moves are sent and stored, and they are checked by
+/// playing them from the snapshot of the stored game.
+async fn append_player_game_moves<S: State + Sync>(
+    ctx: &Context,
+    sender: &S,
+    game_id: &str,
+    player_id: &str,
+    delta: &comm::MoveDelta,
+) -> std::result::Result<HttpResponse, RpcError> {
+    if delta.moves.is_empty() {
+        return Ok(error_response(
+            ErrorCode::InvalidRequest,
+            "No moves to append",
+        ));
+    }
+
+    let request = GetGameRequest {
+        game_id: game_id.to_owned(),
+    };
+
+    let stored = match sender.get_game(ctx, &request).await {
+        Ok(resp) => match resp.success {
+            true => match game_from_response(resp) {
+                Ok(game) => game,
                 Err(err) => return rpc_error_to_http_response(err),
+            },
+            false => return failure_to_http_response(&resp.message.unwrap()),
+        },
+        Err(err) => return rpc_error_to_http_response(err),
+    };
+
+    if stored.competitive {
+        return Ok(error_response(
+            ErrorCode::Conflict,
+            "Competitive games only accept moves through the moves route",
+        ));
+    }
+
+    let request = GetPlayerGameRequest {
+        game_id: game_id.to_owned(),
+        player_id: player_id.to_owned(),
+    };
+
+    let (moves, score, revision, snapshot) = match sender.get_player_game(ctx, &request).await {
+        Ok(resp) => match resp.success {
+            true => (
+                resp.moves.unwrap(),
+                resp.score.unwrap(),
+                resp.revision.unwrap(),
+                resp.snapshot,
+            ),
+            false => return failure_to_http_response(&resp.message.unwrap()),
+        },
+        Err(err) => return rpc_error_to_http_response(err),
+    };
+
+    if revision != delta.revision {
+        return Ok(error_response(
+            ErrorCode::RevisionMismatch,
+            "Stored game is not at the given revision",
+        ));
+    }
+
+    // Only the new moves are played, from the snapshot stored with the game.
+    // Games stored without a snapshot are replayed once to take one.
+    let snapshot = match snapshot.and_then(|s| serde_json::from_str::<Snapshot>(&s).ok()) {
+        Some(snapshot) => Ok(snapshot),
+        None => GameExchange::new(
+            String::new(),
+            game_id.to_owned(),
+            score,
+            stored.seed.clone(),
+            stored.size as usize,
+            &moves,
+        )
+        .map_err(|_| "Invalid moves")
+        .and_then(|mut gx| {
+            gx.set_topology(stored.topology);
+            gx.set_blockers(stored.blockers);
+            Game::try_from(&gx)
+        })
+        .map(|game| game.snapshot()),
+    };
+
+    let resumed = snapshot.and_then(|snapshot| {
+        stored
+            .seed
+            .parse()
+            .ok()
+            .and_then(|seed| snapshot.resume(game_id, seed, stored.topology))
+            .map(|game| (snapshot, game))
+            .ok_or("Invalid snapshot")
+    });
+
+    let (snapshot, mut game) = match resumed {
+        Ok(resumed) => resumed,
+        Err(message) => return Ok(error_response(ErrorCode::Internal, message)),
+    };
+
+    for d in delta.moves.iter() {
+        match game.play(d.clone()).game() {
+            Some(g) => game = g,
+            None => {
+                return Ok(error_response(
+                    ErrorCode::IllegalMove,
+                    &format!("Move {} is not legal", d),
+                ))
+            }
+        }
+    }
+
+    let snapshot = snapshot.after(&game);
+
+    let request = AppendPlayerGameMovesRequest {
+        game_id: game_id.to_owned(),
+        game_over: Some(game.get_game_over()),
+        max_tile: Some(game.max_tile()),
+        moves: serde_json::to_string(&delta.moves).unwrap(),
+        player_id: player_id.to_owned(),
+        revision,
+        score: game.get_score(),
+        snapshot: Some(serde_json::to_string(&snapshot).unwrap()),
+    };
+
+    match sender.append_player_game_moves(ctx, &request).await {
+        Ok(resp) => match resp.success {
+            true => {
+                let result = comm::MoveDeltaResult {
+                    score: game.get_score(),
+                    game_over: game.get_game_over(),
+                    move_count: snapshot.move_count,
+                };
+                Ok(player_game_response(
+                    HttpResponseCodes::Ok as u16,
+                    serde_json::to_string(&result).unwrap().as_bytes().to_vec(),
+                    &resp.revision.unwrap(),
+                ))
             }
+            // The game was updated since it was read
+            false => Ok(error_response(
+                ErrorCode::RevisionMismatch,
+                "Stored game is not at the given revision",
+            )),
+        },
+        Err(err) => rpc_error_to_http_response(err),
+    }
+}
+
+/// Stores a batch of games queued by a client while offline, each one as if it
+/// was sent with `PUT`. Games which conflict with the stored game are returned
+/// with the stored copy, so that the client can resolve them.
+async fn handle_sync_player_games(
+    ctx: &Context,
+    params: &Params,
+    body: &Vec<u8>,
+) -> std::result::Result<HttpResponse, RpcError> {
+    let player_id = params.find(PLAYER_ID_KEY).unwrap();
+
+    let batch = match serde_json::from_slice::<comm::SyncBatch>(body) {
+        Ok(batch) => batch,
+        Err(_) => {
+            return Ok(error_response(
+                ErrorCode::InvalidRequest,
+                "Error parsing request body",
+            ))
         }
+    };
+
+    if batch.games.len() > MAX_SYNC_GAMES {
+        return Ok(error_response(
+            ErrorCode::InvalidRequest,
+            &format!("Sync batches are limited to {} games", MAX_SYNC_GAMES),
+        ));
+    }
+
+    let sender = StateSender::new();
+
+    let mut results = Vec::with_capacity(batch.games.len());
+    for gx in batch.games {
+        let game_id = gx.get_id();
+        let result = match store_player_game(ctx, player_id, &game_id, gx, None).await {
+            Ok(Ok(stored)) => comm::SyncResult {
+                id: game_id,
+                status: comm::SyncStatus::Accepted,
+                revision: Some(stored.revision),
+                game: None,
+                error: None,
+            },
+            Ok(Err(error)) if error.code == ErrorCode::Conflict => {
+                match load_player_game(ctx, &sender, &game_id, player_id).await {
+                    Ok(Ok(loaded)) if !loaded.seed_secret => comm::SyncResult {
+                        id: game_id,
+                        status: comm::SyncStatus::Conflict,
+                        revision: Some(loaded.revision),
+                        game: Some(loaded.gx),
+                        error: Some(error),
+                    },
+                    // Conflicts with the parameters of a game the player has
+                    // not stored, or with a game whose seed is secret, cannot
+                    // be resolved by the client
+                    Ok(_) => comm::SyncResult {
+                        id: game_id,
+                        status: comm::SyncStatus::Rejected,
+                        revision: None,
+                        game: None,
+                        error: Some(error),
+                    },
+                    Err(err) => return rpc_error_to_http_response(err),
+                }
+            }
+            Ok(Err(error)) => comm::SyncResult {
+                id: game_id,
+                status: comm::SyncStatus::Rejected,
+                revision: None,
+                game: None,
+                error: Some(error),
+            },
+            Err(err) => return rpc_error_to_http_response(err),
+        };
+        results.push(result);
     }
+
+    Ok(HttpResponse {
+        body: serde_json::to_string(&comm::SyncBatchResult { results })
+            .unwrap()
+            .as_bytes()
+            .to_vec(),
+        ..Default::default()
+    })
 }
 
 async fn handle_get_player_games(
@@ -388,6 +1463,7 @@ async fn handle_get_player(
                 let player = comm::Player {
                     display_name: resp.display_name.unwrap(),
                     last_change: DateTime::<Utc>::try_from(resp.last_change.unwrap()).unwrap(),
+                    secret: None,
                 };
                 Ok(HttpResponse {
                     body: serde_json::to_string(&player).unwrap().as_bytes().to_vec(),
@@ -400,32 +1476,66 @@ async fn handle_get_player(
     }
 }
 
+async fn handle_get_player_stats(
+    ctx: &Context,
+    params: &Params,
+) -> std::result::Result<HttpResponse, RpcError> {
+    let sender = StateSender::new();
+    let request = GetPlayerStatsRequest {
+        player_id: params.find(PLAYER_ID_KEY).unwrap().to_owned(),
+    };
+
+    match sender.get_player_stats(ctx, &request).await {
+        Ok(resp) => match resp.success {
+            true => {
+                let stats = comm::PlayerStats {
+                    games_played: resp.games_played.unwrap(),
+                    games_finished: resp.games_finished.unwrap(),
+                    best_score: resp.best_score.unwrap(),
+                    average_score: resp.average_score.unwrap(),
+                    max_tile: resp.max_tile.unwrap(),
+                    total_moves: resp.total_moves.unwrap(),
+                };
+                Ok(HttpResponse {
+                    body: serde_json::to_string(&stats).unwrap().as_bytes().to_vec(),
+                    ..Default::default()
+                })
+            }
+            false => failure_to_http_response(&resp.message.unwrap()),
+        },
+        Err(err) => rpc_error_to_http_response(err),
+    }
+}
+
+/// Updates the player, or creates it if it does not exist. A secret is issued
+/// to new players and returned with the player. Players with a secret can
+/// only be updated with a verified bearer token, while players created before
+/// secrets existed are updated without one until they are claimed.
 async fn handle_update_player(
     ctx: &Context,
     params: &Params,
     body: &Vec<u8>,
+    verified: bool,
 ) -> std::result::Result<HttpResponse, RpcError> {
     let player_id = params.find(PLAYER_ID_KEY).unwrap();
 
     let body_str = match std::str::from_utf8(&body) {
         Ok(s) => s,
         Err(_) => {
-            return Ok(HttpResponse {
-                status_code: HttpResponseCodes::BadRequest as u16,
-                body: "Error parsing request body as string".as_bytes().to_vec(),
-                ..Default::default()
-            })
+            return Ok(error_response(
+                ErrorCode::InvalidRequest,
+                "Error parsing request body as string",
+            ))
         }
     };
 
     let player = match serde_json::from_str::<comm::Player>(body_str) {
         Ok(player) => player,
         Err(_) => {
-            return Ok(HttpResponse {
-                status_code: HttpResponseCodes::BadRequest as u16,
-                body: "Error parsing request body as player".as_bytes().to_vec(),
-                ..Default::default()
-            })
+            return Ok(error_response(
+                ErrorCode::InvalidRequest,
+                "Error parsing request body as player",
+            ))
         }
     };
 
@@ -439,54 +1549,60 @@ async fn handle_update_player(
         match sender.get_player(ctx, &request).await {
             Ok(gp_resp) => match gp_resp.success {
                 true => {
+                    if gp_resp.secret.is_some() && !verified {
+                        return Ok(unauthorized_response("Invalid or missing bearer token"));
+                    }
+
                     let request = UpdatePlayerRequest {
                         display_name: player.display_name.clone(),
                         last_change: Timestamp::from(player.last_change),
                         player_id: player_id.to_owned(),
                     };
 
-                    match sender.update_player(ctx, &request).await {
+                    let player = match sender.update_player(ctx, &request).await {
                         Ok(resp) => match resp.success {
-                            true => {
-                                return Ok(HttpResponse {
-                                    body: body.clone(),
-                                    ..Default::default()
-                                })
-                            }
-                            false => {
-                                let player = Player {
-                                    display_name: gp_resp.display_name.unwrap().clone(),
-                                    last_change: DateTime::<Utc>::try_from(
-                                        gp_resp.last_change.unwrap(),
-                                    )
-                                    .unwrap(),
-                                };
-                                return Ok(HttpResponse {
-                                    body: serde_json::to_string(&player)
-                                        .unwrap()
-                                        .as_bytes()
-                                        .to_vec(),
-                                    ..Default::default()
-                                });
-                            }
+                            true => player,
+                            false => Player {
+                                display_name: gp_resp.display_name.unwrap().clone(),
+                                last_change: DateTime::<Utc>::try_from(
+                                    gp_resp.last_change.unwrap(),
+                                )
+                                .unwrap(),
+                                secret: None,
+                            },
                         },
                         Err(err) => return rpc_error_to_http_response(err),
-                    }
+                    };
+
+                    return Ok(HttpResponse {
+                        body: serde_json::to_string(&player).unwrap().as_bytes().to_vec(),
+                        ..Default::default()
+                    });
                 }
                 false => {
+                    let secret = new_secret().await?;
                     let request = CreatePlayerRequest {
                         display_name: player.display_name.clone(),
                         last_change: Timestamp::from(player.last_change),
                         player_id: player_id.to_owned(),
+                        secret: Some(secret.clone()),
                     };
 
                     match sender.create_player(ctx, &request).await {
                         Ok(resp) => match resp.success {
                             true => {
+                                let player = Player {
+                                    secret: Some(secret),
+                                    ..player
+                                };
                                 return Ok(HttpResponse {
-                                    body: body.clone(),
+                                    status_code: HttpResponseCodes::Created as u16,
+                                    body: serde_json::to_string(&player)
+                                        .unwrap()
+                                        .as_bytes()
+                                        .to_vec(),
                                     ..Default::default()
-                                })
+                                });
                             }
                             false => (),
                         },
@@ -499,23 +1615,118 @@ async fn handle_update_player(
     }
 }
 
-async fn handle_get_player_game_leaderboard(
+async fn handle_claim_player(
     ctx: &Context,
     params: &Params,
 ) -> std::result::Result<HttpResponse, RpcError> {
-    let game_id = params.find(GAME_ID_KEY).unwrap();
     let player_id = params.find(PLAYER_ID_KEY).unwrap();
+    let secret = new_secret().await?;
 
-    let sender = StateSender::new();
+    claim_player(ctx, &StateSender::new(), player_id, secret).await
+}
 
-    let request = GetLeaderboardRequest {
-        game_id: game_id.to_owned(),
+/// Issues a secret to a player created before secrets existed, and returns it
+/// with the player. Until then the player ID is the only credential of such a
+/// player, so whoever claims it first owns it; clients claim their player as
+/// soon as they find it without a secret.
+async fn claim_player<S: State + Sync>(
+    ctx: &Context,
+    state: &S,
+    player_id: &str,
+    secret: String,
+) -> std::result::Result<HttpResponse, RpcError> {
+    let request = GetPlayerRequest {
+        player_id: player_id.to_owned(),
+    };
+
+    let stored = match state.get_player(ctx, &request).await {
+        Ok(resp) if resp.success => resp,
+        Ok(resp) => return failure_to_http_response(&resp.message.unwrap()),
+        Err(err) => return rpc_error_to_http_response(err),
+    };
+    if stored.secret.is_some() {
+        return Ok(error_response(
+            ErrorCode::Conflict,
+            "Player is already claimed",
+        ));
+    }
+
+    let request = ClaimPlayerRequest {
         player_id: player_id.to_owned(),
+        secret: secret.clone(),
     };
 
-    let leaderboard = match sender.get_leaderboard(ctx, &request).await {
+    match state.claim_player(ctx, &request).await {
+        Ok(resp) if resp.success => {
+            let player = Player {
+                display_name: stored.display_name.unwrap(),
+                last_change: DateTime::<Utc>::try_from(stored.last_change.unwrap()).unwrap(),
+                secret: Some(secret),
+            };
+            Ok(HttpResponse {
+                body: serde_json::to_string(&player).unwrap().as_bytes().to_vec(),
+                ..Default::default()
+            })
+        }
+        // Claimed by another request since it was read
+        Ok(_) => Ok(error_response(
+            ErrorCode::Conflict,
+            "Player is already claimed",
+        )),
+        Err(err) => rpc_error_to_http_response(err),
+    }
+}
+
+/// The page of a leaderboard selected with the `limit`, `offset` and `around`
+/// query parameters, with the limits of the backend applied.
+struct LeaderboardPage {
+    limit: u32,
+    offset: u32,
+    around: Option<u32>,
+}
+
+impl LeaderboardPage {
+    fn from_query(query_string: &str) -> std::result::Result<Self, HttpResponse> {
+        let mut page = LeaderboardPage {
+            limit: MAX_LEADERBOARD_LIMIT,
+            offset: 0,
+            around: None,
+        };
+        for (key, value) in form_urlencoded::parse(query_string.as_bytes()) {
+            let target = match key.as_ref() {
+                "limit" => &mut page.limit,
+                "offset" => &mut page.offset,
+                "around" => page.around.get_or_insert(0),
+                _ => continue,
+            };
+            match value.parse() {
+                Ok(v) => *target = v,
+                Err(_) => {
+                    return Err(error_response(
+                        ErrorCode::InvalidRequest,
+                        &format!("Invalid value for {}", key),
+                    ))
+                }
+            }
+        }
+        page.limit = page.limit.min(MAX_LEADERBOARD_LIMIT);
+        page.around = page.around.map(|a| a.min(MAX_LEADERBOARD_AROUND));
+        Ok(page)
+    }
+}
+
+fn leaderboard_to_http_response(
+    result: RpcResult<GetLeaderboardResponse>,
+) -> std::result::Result<HttpResponse, RpcError> {
+    let leaderboard = match result {
         Ok(resp) => match resp.success {
-            true => resp.leaderboard.unwrap(),
+            true => comm::Leaderboard {
+                entries: resp.leaderboard.unwrap(),
+                total: resp.total.unwrap(),
+                rank: resp.rank,
+                around_me: resp.around_me,
+                around_offset: resp.around_offset,
+            },
             false => return failure_to_http_response(&resp.message.unwrap()),
         },
         Err(err) => return rpc_error_to_http_response(err),
@@ -530,87 +1741,1869 @@ async fn handle_get_player_game_leaderboard(
     })
 }
 
-async fn handle_get_game(
+/// Returns a page of the leaderboard of a game. The page is selected with the
+/// `limit` and `offset` query parameters, and `around` adds the given number
+/// of entries before and after the requesting player.
+async fn handle_get_player_game_leaderboard(
     ctx: &Context,
     params: &Params,
+    query_string: &str,
 ) -> std::result::Result<HttpResponse, RpcError> {
     let game_id = params.find(GAME_ID_KEY).unwrap();
+    let player_id = params.find(PLAYER_ID_KEY).unwrap();
+
+    let page = match LeaderboardPage::from_query(query_string) {
+        Ok(page) => page,
+        Err(resp) => return Ok(resp),
+    };
 
     let sender = StateSender::new();
 
-    let request = GetGameRequest {
+    let request = GetLeaderboardRequest {
+        around: page.around,
         game_id: game_id.to_owned(),
+        limit: Some(page.limit),
+        offset: Some(page.offset),
+        player_id: player_id.to_owned(),
     };
 
-    let game = match sender.get_game(ctx, &request).await {
-        Ok(resp) => match resp.success {
-            true => comm::Game {
-                seed: resp.seed.unwrap().to_string(),
-                size: resp.size.unwrap(),
-            },
-            false => return failure_to_http_response(&resp.message.unwrap()),
-        },
-        Err(err) => return rpc_error_to_http_response(err),
+    leaderboard_to_http_response(sender.get_leaderboard(ctx, &request).await)
+}
+
+/// Returns a page of a leaderboard of the best single game results across
+/// all games in the current period, selected with the same query parameters
+/// as the leaderboards of games. The requesting player, if any, is given with
+/// the `player` query parameter.
+async fn handle_get_global_leaderboard(
+    ctx: &Context,
+    params: &Params,
+    query_string: &str,
+) -> std::result::Result<HttpResponse, RpcError> {
+    let period = match params.find(PERIOD_KEY).unwrap() {
+        "all-time" => "AllTime",
+        "weekly" => "Weekly",
+        "daily" => "Daily",
+        _ => return failure_to_http_response("Unknown leaderboard period"),
+    };
+    let metric = match params.find(METRIC_KEY).unwrap() {
+        "score" => "Score",
+        "tile" => "Tile",
+        _ => return failure_to_http_response("Unknown leaderboard metric"),
     };
 
-    Ok(HttpResponse {
-        body: serde_json::to_string(&game).unwrap().as_bytes().to_vec(),
-        ..Default::default()
-    })
-}
+    let page = match LeaderboardPage::from_query(query_string) {
+        Ok(page) => page,
+        Err(resp) => return Ok(resp),
+    };
+    let player_id = form_urlencoded::parse(query_string.as_bytes())
+        .find(|(key, _)| key == "player")
+        .map(|(_, value)| value.into_owned());
 
+    let sender = StateSender::new();
+
+    let request = GetGlobalLeaderboardRequest {
+        around: page.around,
+        limit: Some(page.limit),
+        metric: metric.to_owned(),
+        offset: Some(page.offset),
+        period: period.to_owned(),
+        player_id,
+    };
+
+    leaderboard_to_http_response(sender.get_global_leaderboard(ctx, &request).await)
+}
+
+async fn handle_get_game(
+    ctx: &Context,
+    params: &Params,
+) -> std::result::Result<HttpResponse, RpcError> {
+    let game_id = params.find(GAME_ID_KEY).unwrap();
+
+    let sender = StateSender::new();
+
+    let request = GetGameRequest {
+        game_id: game_id.to_owned(),
+    };
+
+    let mut game = match sender.get_game(ctx, &request).await {
+        Ok(resp) => match resp.success {
+            true => match game_from_response(resp) {
+                Ok(game) => game,
+                Err(err) => return rpc_error_to_http_response(err),
+            },
+            false => return failure_to_http_response(&resp.message.unwrap()),
+        },
+        Err(err) => return rpc_error_to_http_response(err),
+    };
+
+    if let Err(err) = reveal_if_due(ctx, &sender, game_id, &mut game).await {
+        return rpc_error_to_http_response(err);
+    }
+
+    // The seed of a competitive game and the nonce of its commitment are
+    // kept secret until it is revealed
+    let mut value = serde_json::to_value(&game).unwrap();
+    if game.competitive && game.status != GameStatus::Revealed {
+        value.as_object_mut().unwrap().remove("seed");
+        value.as_object_mut().unwrap().remove("nonce");
+    }
+
+    Ok(HttpResponse {
+        body: serde_json::to_string(&value).unwrap().as_bytes().to_vec(),
+        ..Default::default()
+    })
+}
+
+async fn handle_create_competitive_game(
+    ctx: &Context,
+    body: &[u8],
+) -> std::result::Result<HttpResponse, RpcError> {
+    let params = match serde_json::from_slice::<comm::NewCompetitiveGame>(body) {
+        Ok(params) => params,
+        Err(_) => {
+            return Ok(error_response(
+                ErrorCode::InvalidRequest,
+                "Error parsing request body",
+            ))
+        }
+    };
+
+    if params.size != 4 {
+        return Ok(error_response(
+            ErrorCode::InvalidGame,
+            "Only games of size 4 are allowed",
+        ));
+    }
+
+    if Game::new_unseeded(
+        params.size as usize,
+        "",
+        params.topology,
+        params.blockers.clone(),
+    )
+    .is_none()
+    {
+        return Ok(error_response(ErrorCode::InvalidGame, "Invalid blockers"));
+    }
+
+    if params.blocker_count > 0 && !params.blockers.is_empty() {
+        return Ok(error_response(
+            ErrorCode::InvalidGame,
+            "Blockers and a blocker count cannot both be given",
+        ));
+    }
+
+    if params.reveal_at.is_some_and(|t| t <= Utc::now()) {
+        return Ok(error_response(
+            ErrorCode::InvalidGame,
+            "Reveal time must be in the future",
+        ));
+    }
+
+    let sender = StateSender::new();
+
+    // The seed is drawn by the server and never sent to players, so that
+    // they cannot simulate the spawned tiles
+    loop {
+        let game_id = wasmcloud_interface_numbergen::generate_guid().await?;
+        let seed = (wasmcloud_interface_numbergen::random_32().await? as u64) << 32
+            | wasmcloud_interface_numbergen::random_32().await? as u64;
+        let nonce = match params.reveal_at {
+            Some(_) => Some(random_hex(4).await?),
+            None => None,
+        };
+        let commitment = nonce
+            .as_deref()
+            .map(|nonce| engine::seed_commitment(&game_id, seed, nonce));
+        let blockers = match params.blocker_count {
+            0 => params.blockers.clone(),
+            count => Game::blockers_from_seed(params.size as usize, seed, count),
+        };
+
+        let request = CreateGameRequest {
+            game_id: game_id.clone(),
+            seed,
+            size: params.size,
+            topology: Some(params.topology.to_string()),
+            blockers: Some(serde_json::to_string(&blockers).unwrap()),
+            competitive: Some(true),
+            commitment: commitment.clone(),
+            nonce,
+            reveal_at: params.reveal_at.map(Timestamp::from),
+        };
+
+        match sender.create_game(ctx, &request).await {
+            Ok(resp) => match resp.success {
+                true => {
+                    let game = comm::CompetitiveGame {
+                        id: game_id,
+                        size: params.size,
+                        topology: params.topology,
+                        blockers,
+                        commitment,
+                        reveal_at: params.reveal_at,
+                    };
+                    let mut header = HashMap::new();
+                    header.insert("Location".to_owned(), vec![format!("/games/{}", game.id)]);
+                    return Ok(HttpResponse {
+                        status_code: HttpResponseCodes::Created as u16,
+                        header,
+                        body: serde_json::to_string(&game).unwrap().as_bytes().to_vec(),
+                    });
+                }
+                false => (),
+            },
+            Err(err) => return rpc_error_to_http_response(err),
+        }
+    }
+}
+
+async fn handle_post_player_game_moves(
+    ctx: &Context,
+    params: &Params,
+    body: &[u8],
+) -> std::result::Result<HttpResponse, RpcError> {
+    let game_id = params.find(GAME_ID_KEY).unwrap();
+    let player_id = params.find(PLAYER_ID_KEY).unwrap();
+
+    let batch = match serde_json::from_slice::<comm::MoveBatch>(body) {
+        Ok(batch) => batch,
+        Err(_) => {
+            return Ok(error_response(
+                ErrorCode::InvalidRequest,
+                "Error parsing request body",
+            ))
+        }
+    };
+
+    post_player_game_moves(ctx, &StateSender::new(), game_id, player_id, &batch).await
+}
+
+/// Applies a batch of moves to a competitive game on the server and returns
+/// the spawned tiles. An empty batch returns the current board, which starts
+/// the game if the player has not played it yet.
+async fn post_player_game_moves<S: State + Sync>(
+    ctx: &Context,
+    state: &S,
+    game_id: &str,
+    player_id: &str,
+    batch: &comm::MoveBatch,
+) -> std::result::Result<HttpResponse, RpcError> {
+    let request = GetGameRequest {
+        game_id: game_id.to_owned(),
+    };
+
+    let stored = match state.get_game(ctx, &request).await {
+        Ok(resp) => match resp.success {
+            true => match game_from_response(resp) {
+                Ok(game) => game,
+                Err(err) => return rpc_error_to_http_response(err),
+            },
+            false => return failure_to_http_response(&resp.message.unwrap()),
+        },
+        Err(err) => return rpc_error_to_http_response(err),
+    };
+
+    if !stored.competitive {
+        return Ok(error_response(
+            ErrorCode::Conflict,
+            "Game is not competitive",
+        ));
+    }
+
+    if stored.reveal_at.is_some_and(|t| t <= Utc::now()) {
+        return Ok(error_response(
+            ErrorCode::Forbidden,
+            "Competition is closed",
+        ));
+    }
+
+    loop {
+        let request = GetPlayerGameRequest {
+            game_id: game_id.to_owned(),
+            player_id: player_id.to_owned(),
+        };
+
+        let player_game_data = match state.get_player_game(ctx, &request).await {
+            Ok(resp) => match resp.success {
+                true => Some((
+                    resp.moves.unwrap(),
+                    resp.score.unwrap(),
+                    resp.revision.unwrap(),
+                )),
+                false => None,
+            },
+            Err(err) => return rpc_error_to_http_response(err),
+        };
+
+        let old_game = match &player_game_data {
+            Some((moves, score, _)) => GameExchange::new(
+                String::new(),
+                game_id.to_owned(),
+                *score,
+                stored.seed.clone(),
+                stored.size as usize,
+                moves,
+            )
+            .map_err(|_| "Invalid moves")
+            .and_then(|mut gx| {
+                gx.set_topology(stored.topology);
+                gx.set_blockers(stored.blockers.clone());
+                Game::try_from(&gx)
+            }),
+            None => stored
+                .seed
+                .parse()
+                .map_err(|_| "Invalid seed")
+                .and_then(|seed| {
+                    Game::new_from_definition(
+                        stored.size as usize,
+                        seed,
+                        game_id,
+                        stored.topology,
+                        stored.blockers.clone(),
+                    )
+                    .ok_or("Invalid game definition")
+                }),
+        };
+
+        let mut game = match old_game {
+            Ok(game) => game,
+            Err(message) => return Ok(error_response(ErrorCode::Internal, message)),
+        };
+
+        let from = game.get_moves().len();
+        let mut spawns = vec![];
+        for d in batch.moves.iter() {
+            let result = game.play(d.clone());
+            match result.game() {
+                Some(g) => {
+                    spawns.extend(result.spawn());
+                    game = g;
+                }
+                None => {
+                    return Ok(error_response(
+                        ErrorCode::IllegalMove,
+                        &format!("Move {} is not legal", d),
+                    ))
+                }
+            }
+        }
+
+        let gx = GameExchange::from_game(&game);
+        let stored_ok = match player_game_data {
+            Some((_, _, revision)) => {
+                if batch.moves.is_empty() {
+                    true
+                } else {
+                    let request = UpdatePlayerGameRequest {
+                        game_id: game_id.to_owned(),
+                        game_over: Some(game.get_game_over()),
+                        max_tile: Some(game.max_tile()),
+                        moves: gx.get_moves_str(),
+                        player_id: player_id.to_owned(),
+                        snapshot: Some(serde_json::to_string(&game.snapshot()).unwrap()),
+                        revision,
+                        score: game.get_score(),
+                    };
+                    match state.update_player_game(ctx, &request).await {
+                        Ok(resp) => resp.success,
+                        Err(err) => return rpc_error_to_http_response(err),
+                    }
+                }
+            }
+            None => {
+                let request = CreatePlayerGameRequest {
+                    game_id: game_id.to_owned(),
+                    game_over: Some(game.get_game_over()),
+                    max_tile: Some(game.max_tile()),
+                    moves: gx.get_moves_str(),
+                    player_id: player_id.to_owned(),
+                    snapshot: Some(serde_json::to_string(&game.snapshot()).unwrap()),
+                    score: game.get_score(),
+                };
+                match state.create_player_game(ctx, &request).await {
+                    Ok(resp) => resp.success,
+                    Err(err) => return rpc_error_to_http_response(err),
+                }
+            }
+        };
+
+        if stored_ok {
+            // Competitions reveal the seed which the spawns are signed with,
+            // so that players can show which tiles they were sent
+            let signature = stored.commitment.as_ref().map(|_| {
+                engine::spawn_signature(game.get_seed(), game_id, player_id, from, &spawns)
+            });
+            let result = comm::MoveBatchResult {
+                spawns,
+                score: game.get_score(),
+                game_over: game.get_game_over(),
+                move_count: game.get_moves().len(),
+                layout: game.layout(),
+                signature,
+            };
+            return Ok(HttpResponse {
+                body: serde_json::to_string(&result).unwrap().as_bytes().to_vec(),
+                ..Default::default()
+            });
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 enum Requests {
     GetPlayer,
     UpdatePlayer,
+    ClaimPlayer,
     GetPlayerGame,
     UpdatePlayerGame,
+    AppendPlayerGameMoves,
     GetPlayerGames,
     GetPlayerGameLeaderboard,
+    GetPlayerGameReplay,
+    GetPlayerGameFrames,
+    WatchPlayerGame,
+    GetPlayerGameRace,
     GetGame,
+    CreateCompetitiveGame,
+    PostPlayerGameMoves,
+    GetGlobalLeaderboard,
+    GetPlayerStats,
+    SyncPlayerGames,
+    GetOpenApi,
 }
 
-/// Implementation of HttpServer trait methods
-#[async_trait]
-impl HttpServer for Twenty48BackendActor {
-    async fn handle_request(
-        &self,
-        ctx: &Context,
-        req: &HttpRequest,
-    ) -> std::result::Result<HttpResponse, RpcError> {
-        let mut router = Router::<Requests>::new();
-
-        router.add(&GET_PLAYER_GAME_ROUTE, Requests::GetPlayerGame);
-        router.add(&PUT_PLAYER_GAME_ROUTE, Requests::UpdatePlayerGame);
-        router.add(&GET_PLAYER_GAMES_ROUTE, Requests::GetPlayerGames);
-        router.add(&GET_PLAYER_ROUTE, Requests::GetPlayer);
-        router.add(&UPDATE_PLAYER_ROUTE, Requests::UpdatePlayer);
-        router.add(
-            &GET_PLAYER_GAME_LEADERBOARD_ROUTE,
+/// Every route of the API with the request it is handled as. The router and
+/// the OpenAPI description are both built from this table.
+fn routes() -> Vec<(&'static str, Requests)> {
+    vec![
+        (GET_PLAYER_GAME_ROUTE.as_str(), Requests::GetPlayerGame),
+        (PUT_PLAYER_GAME_ROUTE.as_str(), Requests::UpdatePlayerGame),
+        (
+            PATCH_PLAYER_GAME_ROUTE.as_str(),
+            Requests::AppendPlayerGameMoves,
+        ),
+        (GET_PLAYER_GAMES_ROUTE.as_str(), Requests::GetPlayerGames),
+        (GET_PLAYER_STATS_ROUTE.as_str(), Requests::GetPlayerStats),
+        (GET_PLAYER_ROUTE.as_str(), Requests::GetPlayer),
+        (UPDATE_PLAYER_ROUTE.as_str(), Requests::UpdatePlayer),
+        (CLAIM_PLAYER_ROUTE.as_str(), Requests::ClaimPlayer),
+        (
+            GET_PLAYER_GAME_LEADERBOARD_ROUTE.as_str(),
             Requests::GetPlayerGameLeaderboard,
+        ),
+        (
+            GET_PLAYER_GAME_REPLAY_ROUTE.as_str(),
+            Requests::GetPlayerGameReplay,
+        ),
+        (
+            GET_PLAYER_GAME_FRAMES_ROUTE.as_str(),
+            Requests::GetPlayerGameFrames,
+        ),
+        (WATCH_PLAYER_GAME_ROUTE.as_str(), Requests::WatchPlayerGame),
+        (
+            GET_PLAYER_GAME_RACE_ROUTE.as_str(),
+            Requests::GetPlayerGameRace,
+        ),
+        (GET_GAME_ROUTE.as_str(), Requests::GetGame),
+        (
+            POST_PLAYER_GAMES_ROUTE.as_str(),
+            Requests::CreateCompetitiveGame,
+        ),
+        (
+            POST_PLAYER_GAME_MOVES_ROUTE.as_str(),
+            Requests::PostPlayerGameMoves,
+        ),
+        (
+            GET_GLOBAL_LEADERBOARD_ROUTE.as_str(),
+            Requests::GetGlobalLeaderboard,
+        ),
+        (SYNC_PLAYER_GAMES_ROUTE.as_str(), Requests::SyncPlayerGames),
+        (GET_OPENAPI_ROUTE.as_str(), Requests::GetOpenApi),
+    ]
+}
+
+static ROUTER: Lazy<Router<Requests>> = Lazy::new(router);
+
+fn router() -> Router<Requests> {
+    let mut router = Router::new();
+    for (route, request) in routes() {
+        router.add(route, request);
+    }
+    router
+}
+
+/// The OpenAPI description of all routes.
+fn openapi_spec() -> serde_json::Value {
+    let operations: Vec<_> = routes()
+        .into_iter()
+        .map(|(route, request)| (route, request.operation()))
+        .collect();
+    openapi::spec(&operations, error_status)
+}
+
+fn handle_get_openapi() -> std::result::Result<HttpResponse, RpcError> {
+    let mut header = HashMap::new();
+    header.insert(
+        "Content-Type".to_owned(),
+        vec!["application/json".to_owned()],
+    );
+    Ok(HttpResponse {
+        header,
+        body: serde_json::to_string(&openapi_spec())
+            .unwrap()
+            .as_bytes()
+            .to_vec(),
+        ..Default::default()
+    })
+}
+
+impl Requests {
+    /// Description of the route for the OpenAPI document, with example
+    /// payloads from which the schemas are inferred. Payloads with optional
+    /// fields have an example with and an example without them.
+    fn operation(&self) -> openapi::Operation {
+        let game_id = "51d5147b-1b18-4a45-9172-5ddba30f73e8";
+        let revision = "9e7628a3-8505-404a-8e7f-4b43dc841416";
+        let time = "2022-01-28T10:01:00Z".parse::<DateTime<Utc>>().unwrap();
+        let nonce = "0".repeat(32);
+        let commitment = engine::seed_commitment(game_id, 42, &nonce);
+        let player = Player {
+            display_name: "Alice".to_owned(),
+            last_change: time,
+            secret: None,
+        };
+        let claimed = Player {
+            secret: Some("0".repeat(64)),
+            ..player.clone()
+        };
+        let plain_gx = GameExchange::new(
+            String::new(),
+            game_id.to_owned(),
+            4,
+            "42".to_owned(),
+            4,
+            "[\"Up\"]",
+        )
+        .unwrap();
+        let mut gx = plain_gx.clone();
+        gx.set_blockers(vec![5]);
+        let secret_gx = LoadedGame {
+            gx: GameExchange::from_game(&Game::new_from_seed(4, 42, game_id)),
+            revision: revision.to_owned(),
+            seed_secret: true,
+        };
+        let gxs = openapi::examples(&[&gx, &plain_gx]);
+        let entry = LeaderboardEntry {
+            display_name: "Alice".to_owned(),
+            max_tile: Some(256),
+            rank: Some(1),
+            requesting_player: true,
+            score: Some(2048),
+        };
+        let leaderboards = openapi::examples(&[
+            comm::Leaderboard {
+                entries: vec![entry.clone()],
+                total: 1,
+                rank: Some(1),
+                around_me: Some(vec![entry]),
+                around_offset: Some(0),
+            },
+            comm::Leaderboard {
+                entries: vec![LeaderboardEntry {
+                    display_name: "Bob".to_owned(),
+                    ..Default::default()
+                }],
+                total: 1,
+                rank: None,
+                around_me: None,
+                around_offset: None,
+            },
+        ]);
+        let competition = comm::Game {
+            seed: "42".to_owned(),
+            size: 4,
+            topology: Topology::Classic,
+            blockers: vec![5],
+            competitive: true,
+            commitment: Some(commitment.clone()),
+            nonce: Some(nonce),
+            reveal_at: Some(time),
+            status: GameStatus::Revealed,
+        };
+        // Sent without the seed and the nonce until they are revealed
+        let mut unrevealed = serde_json::to_value(comm::Game {
+            status: GameStatus::Open,
+            ..competition.clone()
+        })
+        .unwrap();
+        let object = unrevealed.as_object_mut().unwrap();
+        object.remove("seed");
+        object.remove("nonce");
+        let new_competition = comm::NewCompetitiveGame {
+            size: 4,
+            topology: Topology::Classic,
+            blockers: vec![5],
+            blocker_count: 0,
+            reveal_at: Some(time),
+        };
+        let created_competition = comm::CompetitiveGame {
+            id: game_id.to_owned(),
+            size: 4,
+            topology: Topology::Classic,
+            blockers: vec![5],
+            commitment: Some(commitment),
+            reveal_at: Some(time),
+        };
+        let batch_result = comm::MoveBatchResult {
+            spawns: vec![Spawn::new(3, 1)],
+            score: 4,
+            game_over: false,
+            move_count: 1,
+            layout: vec![0; 16],
+            signature: Some("9f86d081884c7d65".to_owned()),
+        };
+        let race = GhostRace {
+            score_diff: vec![0, 4],
+            divergence: Some(1),
+            tiles: vec![
+                TileRace {
+                    value: 4,
+                    first: Some(1),
+                    second: None,
+                },
+                TileRace {
+                    value: 8,
+                    first: None,
+                    second: Some(2),
+                },
+            ],
+        };
+
+        use ErrorCode::*;
+        let (id, summary, request, status, response, errors): (_, _, _, _, _, &[ErrorCode]) =
+            match self {
+                Requests::GetPlayer => (
+                    "getPlayer",
+                    "Get a player",
+                    None,
+                    200,
+                    Body::Json(openapi::example(&player)),
+                    &[NotFound],
+                ),
+                Requests::UpdatePlayer => (
+                    "updatePlayer",
+                    "Create or update a player, issuing a secret to new players",
+                    Some(Body::Json(openapi::example(&player))),
+                    200,
+                    Body::Json(openapi::examples(&[&claimed, &player])),
+                    &[],
+                ),
+                Requests::ClaimPlayer => (
+                    "claimPlayer",
+                    "Issue a secret to a player created before secrets existed",
+                    None,
+                    200,
+                    Body::Json(openapi::example(&claimed)),
+                    &[NotFound, Conflict],
+                ),
+                Requests::GetPlayerGame => (
+                    "getPlayerGame",
+                    "Get a game of a player, tagged with its revision, with spawned tiles instead of a secret seed",
+                    None,
+                    200,
+                    Body::Json(
+                        [
+                            gxs.clone(),
+                            vec![serde_json::from_slice(&secret_gx.to_json()).unwrap()],
+                        ]
+                        .concat(),
+                    ),
+                    &[Forbidden, NotFound],
+                ),
+                Requests::UpdatePlayerGame => (
+                    "updatePlayerGame",
+                    "Store a game of a player, honouring If-Match",
+                    Some(Body::Json(gxs.clone())),
+                    200,
+                    Body::Json(gxs.clone()),
+                    &[NotFound, Conflict, RevisionMismatch, InvalidGame],
+                ),
+                Requests::AppendPlayerGameMoves => (
+                    "appendPlayerGameMoves",
+                    "Append moves to a game of a player at the given revision",
+                    Some(Body::Json(openapi::example(&comm::MoveDelta {
+                        revision: revision.to_owned(),
+                        moves: vec![Direction::Left],
+                    }))),
+                    200,
+                    Body::Json(openapi::example(&comm::MoveDeltaResult {
+                        score: 8,
+                        game_over: false,
+                        move_count: 2,
+                    })),
+                    &[NotFound, Conflict, RevisionMismatch, IllegalMove],
+                ),
+                Requests::GetPlayerGames => (
+                    "getPlayerGames",
+                    "List the IDs of the games of a player",
+                    None,
+                    200,
+                    Body::Json(openapi::example(&comm::GameIdList {
+                        ids: vec![game_id.to_owned()],
+                    })),
+                    &[NotFound],
+                ),
+                Requests::GetPlayerGameLeaderboard => (
+                    "getPlayerGameLeaderboard",
+                    "Get a page of the leaderboard of a game",
+                    None,
+                    200,
+                    Body::Json(leaderboards.clone()),
+                    &[InvalidRequest, NotFound],
+                ),
+                Requests::GetPlayerGameReplay => (
+                    "getPlayerGameReplay",
+                    "Download a game of a player as a binary replay",
+                    None,
+                    200,
+                    Body::Binary("application/octet-stream"),
+                    &[Forbidden, NotFound],
+                ),
+                Requests::GetPlayerGameFrames => (
+                    "getPlayerGameFrames",
+                    "Get a range of the boards of a game of a player, replayed from its moves",
+                    None,
+                    200,
+                    Body::Json(openapi::example(&comm::ReplayFrames {
+                        frames: vec![
+                            comm::ReplayFrame {
+                                index: 0,
+                                direction: None,
+                                score: 0,
+                                score_gained: 0,
+                                merges: 0,
+                                spawn: None,
+                                game_over: false,
+                                layout: vec![0; 16],
+                            },
+                            comm::ReplayFrame {
+                                index: 1,
+                                direction: Some(Direction::Up),
+                                score: 4,
+                                score_gained: 4,
+                                merges: 1,
+                                spawn: Some(Spawn::new(3, 1)),
+                                game_over: false,
+                                layout: vec![0; 16],
+                            },
+                        ],
+                        total: 2,
+                    })),
+                    &[InvalidRequest, Forbidden, NotFound],
+                ),
+                Requests::WatchPlayerGame => (
+                    "watchPlayerGame",
+                    "Wait for a game of a player to change, and get the moves after from, which is required with since_revision",
+                    None,
+                    200,
+                    Body::Json(openapi::example(&comm::GameUpdate {
+                        revision: revision.to_owned(),
+                        from: 1,
+                        moves: vec![Direction::Left],
+                        score: 8,
+                        game_over: false,
+                        move_count: 2,
+                    })),
+                    &[InvalidRequest, Forbidden, NotFound],
+                ),
+                Requests::GetPlayerGameRace => (
+                    "getPlayerGameRace",
+                    "Compare the game of a player with the game of an opponent move by move",
+                    None,
+                    200,
+                    Body::Json(openapi::examples(&[
+                        &race,
+                        &GhostRace {
+                            divergence: None,
+                            ..race.clone()
+                        },
+                    ])),
+                    &[Forbidden, NotFound],
+                ),
+                Requests::GetGame => (
+                    "getGame",
+                    "Get the definition of a game, without the seed of unrevealed competitive games",
+                    None,
+                    200,
+                    Body::Json(
+                        [
+                            openapi::examples(&[
+                                &competition,
+                                &comm::Game {
+                                    competitive: false,
+                                    commitment: None,
+                                    nonce: None,
+                                    reveal_at: None,
+                                    status: GameStatus::Open,
+                                    ..competition.clone()
+                                },
+                            ]),
+                            vec![unrevealed],
+                        ]
+                        .concat(),
+                    ),
+                    &[NotFound],
+                ),
+                Requests::CreateCompetitiveGame => (
+                    "createCompetitiveGame",
+                    "Create a competitive game with a seed drawn by the server, as a player with a secret",
+                    Some(Body::Json(openapi::examples(&[
+                        &new_competition,
+                        &comm::NewCompetitiveGame {
+                            reveal_at: None,
+                            ..new_competition.clone()
+                        },
+                    ]))),
+                    201,
+                    Body::Json(openapi::examples(&[
+                        &created_competition,
+                        &comm::CompetitiveGame {
+                            commitment: None,
+                            reveal_at: None,
+                            ..created_competition.clone()
+                        },
+                    ])),
+                    &[NotFound, InvalidGame],
+                ),
+                Requests::PostPlayerGameMoves => (
+                    "postPlayerGameMoves",
+                    "Play a batch of moves in a competitive game",
+                    Some(Body::Json(openapi::example(&comm::MoveBatch {
+                        moves: vec![Direction::Up],
+                    }))),
+                    200,
+                    Body::Json(openapi::examples(&[
+                        &batch_result,
+                        &comm::MoveBatchResult {
+                            signature: None,
+                            ..batch_result.clone()
+                        },
+                    ])),
+                    &[Forbidden, NotFound, Conflict, IllegalMove],
+                ),
+                Requests::GetGlobalLeaderboard => (
+                    "getGlobalLeaderboard",
+                    "Get a page of an all-time, weekly or daily leaderboard by score or tile",
+                    None,
+                    200,
+                    Body::Json(leaderboards),
+                    &[InvalidRequest, NotFound],
+                ),
+                Requests::GetPlayerStats => (
+                    "getPlayerStats",
+                    "Get the lifetime statistics of a player",
+                    None,
+                    200,
+                    Body::Json(openapi::example(&comm::PlayerStats {
+                        games_played: 3,
+                        games_finished: 2,
+                        best_score: 2048,
+                        average_score: 1365.5,
+                        max_tile: 256,
+                        total_moves: 600,
+                    })),
+                    &[NotFound],
+                ),
+                Requests::SyncPlayerGames => (
+                    "syncPlayerGames",
+                    "Store a batch of games of a player queued while offline",
+                    Some(Body::Json(openapi::example(&comm::SyncBatch {
+                        games: vec![gx.clone(), plain_gx],
+                    }))),
+                    200,
+                    Body::Json(openapi::example(&comm::SyncBatchResult {
+                        results: vec![
+                            comm::SyncResult {
+                                id: game_id.to_owned(),
+                                status: comm::SyncStatus::Conflict,
+                                revision: Some("3".to_owned()),
+                                game: Some(gx),
+                                error: Some(comm::Error::new(
+                                    ErrorCode::Conflict,
+                                    "Submitted game is not a descendant of the stored game",
+                                )),
+                            },
+                            comm::SyncResult {
+                                id: game_id.to_owned(),
+                                status: comm::SyncStatus::Accepted,
+                                revision: Some("4".to_owned()),
+                                game: None,
+                                error: None,
+                            },
+                        ],
+                    })),
+                    &[NotFound],
+                ),
+                Requests::GetOpenApi => (
+                    "getOpenApi",
+                    "Get this description of the API",
+                    None,
+                    200,
+                    Body::Json(vec![serde_json::json!({ "openapi": "3.0.3" })]),
+                    &[],
+                ),
+            };
+
+        let authenticated = matches!(
+            self,
+            Requests::UpdatePlayer
+                | Requests::CreateCompetitiveGame
+                | Requests::UpdatePlayerGame
+                | Requests::AppendPlayerGameMoves
+                | Requests::PostPlayerGameMoves
+                | Requests::SyncPlayerGames
         );
-        router.add(&GET_GAME_ROUTE, Requests::GetGame);
+        // Errors of the middleware, the rate limits and the authentication,
+        // which do not depend on the handler
+        let mut errors = errors.to_vec();
+        if request.is_some() {
+            errors.extend([InvalidRequest, PayloadTooLarge]);
+        }
+        if authenticated {
+            errors.push(Unauthorized);
+        }
+        errors.extend([RateLimited, Internal]);
+
+        openapi::Operation {
+            id,
+            summary,
+            query: match self {
+                Requests::GetPlayerGameLeaderboard => &["limit", "offset", "around"],
+                Requests::GetGlobalLeaderboard => &["limit", "offset", "around", "player"],
+                Requests::GetPlayerGameFrames => &["from", "to"],
+                Requests::WatchPlayerGame => &["since_revision", "from"],
+                _ => &[],
+            },
+            authenticated,
+            request,
+            status,
+            response,
+            no_content: matches!(self, Requests::WatchPlayerGame),
+            errors,
+        }
+    }
 
-        let route = req.method.clone() + &req.path;
+    /// The default rate limit of the route, which applies per remote address
+    /// and, to requests with a verified bearer token, per player. Writes which
+    /// replay whole games are limited the most.
+    fn rate_limit(&self) -> RateLimit {
+        let (name, capacity, refill_per_minute) = match self {
+            Requests::GetPlayer => ("get_player", 60, 120),
+            Requests::UpdatePlayer => ("update_player", 10, 30),
+            Requests::ClaimPlayer => ("claim_player", 5, 10),
+            Requests::GetPlayerGame => ("get_player_game", 60, 120),
+            Requests::UpdatePlayerGame => ("update_player_game", 20, 60),
+            Requests::AppendPlayerGameMoves => ("append_player_game_moves", 60, 300),
+            Requests::GetPlayerGames => ("get_player_games", 30, 60),
+            Requests::GetPlayerGameLeaderboard => ("get_player_game_leaderboard", 60, 120),
+            Requests::GetPlayerGameReplay => ("get_player_game_replay", 10, 30),
+            Requests::GetPlayerGameFrames => ("get_player_game_frames", 30, 120),
+            Requests::WatchPlayerGame => ("watch_player_game", 30, 60),
+            Requests::GetPlayerGameRace => ("get_player_game_race", 10, 30),
+            Requests::GetGame => ("get_game", 60, 120),
+            Requests::CreateCompetitiveGame => ("create_competitive_game", 5, 10),
+            Requests::PostPlayerGameMoves => ("post_player_game_moves", 120, 600),
+            Requests::GetGlobalLeaderboard => ("get_global_leaderboard", 60, 120),
+            Requests::GetPlayerStats => ("get_player_stats", 30, 60),
+            Requests::SyncPlayerGames => ("sync_player_games", 5, 10),
+            Requests::GetOpenApi => ("get_openapi", 10, 30),
+        };
+        RateLimit {
+            name,
+            capacity,
+            refill_per_minute,
+        }
+    }
+}
 
-        match router.recognize(&route) {
-            Ok(m) => match m.handler() {
+/// Routes a request, checks its rate limit and authentication, and passes it
+/// to its handler. Requests are limited per remote address before they are
+/// authenticated, and per player only once their token is verified, so that
+/// nobody can use up the requests of another player.
+async fn dispatch(ctx: &Context, req: &HttpRequest) -> std::result::Result<HttpResponse, RpcError> {
+    let route = req.method.clone() + &req.path;
+
+    match ROUTER.recognize(&route) {
+        Ok(m) => {
+            let limit = m.handler().rate_limit();
+            let address = remote_address(req);
+            match check_rate_limit(ctx, &limit, &format!("address:{}", address)).await {
+                Ok(Ok(())) => (),
+                Ok(Err(resp)) => return Ok(resp),
+                Err(err) => return rpc_error_to_http_response(err),
+            }
+
+            // Writes are authenticated before they are dispatched
+            let caller = match m.handler() {
+                Requests::UpdatePlayer
+                | Requests::CreateCompetitiveGame
+                | Requests::UpdatePlayerGame
+                | Requests::AppendPlayerGameMoves
+                | Requests::PostPlayerGameMoves
+                | Requests::SyncPlayerGames => match authenticate(ctx, req, m.params()).await {
+                    Ok(Ok(caller)) => caller,
+                    Ok(Err(resp)) => return Ok(resp),
+                    Err(err) => return rpc_error_to_http_response(err),
+                },
+                _ => Caller::Unknown,
+            };
+            let verified = caller == Caller::Verified;
+
+            if verified {
+                let player_id = m.params().find(PLAYER_ID_KEY).unwrap();
+                match check_rate_limit(ctx, &limit, &format!("player:{}", player_id)).await {
+                    Ok(Ok(())) => (),
+                    Ok(Err(resp)) => return Ok(resp),
+                    Err(err) => return rpc_error_to_http_response(err),
+                }
+            }
+
+            match m.handler() {
+                Requests::UpdatePlayerGame
+                | Requests::AppendPlayerGameMoves
+                | Requests::PostPlayerGameMoves
+                | Requests::SyncPlayerGames
+                    if caller == Caller::Unknown =>
+                {
+                    Ok(error_response(
+                        ErrorCode::NotFound,
+                        "Players must be stored before their games",
+                    ))
+                }
+                // Competitions are stored by the server, so only players who
+                // can be held to account may create them
+                Requests::CreateCompetitiveGame if !verified => Ok(unauthorized_response(
+                    "Competitions can only be created by players with a secret",
+                )),
                 Requests::GetPlayerGame => handle_get_player_game(ctx, m.params()).await,
                 Requests::UpdatePlayerGame => {
-                    handle_update_player_game(ctx, m.params(), &req.body).await
+                    handle_update_player_game(ctx, m.params(), &req.body, header(req, "If-Match"))
+                        .await
+                }
+                Requests::AppendPlayerGameMoves => {
+                    handle_append_player_game_moves(ctx, m.params(), &req.body).await
                 }
                 Requests::GetPlayerGames => handle_get_player_games(ctx, m.params()).await,
                 Requests::GetPlayer => handle_get_player(ctx, m.params()).await,
-                Requests::UpdatePlayer => handle_update_player(ctx, m.params(), &req.body).await,
+                Requests::GetPlayerStats => handle_get_player_stats(ctx, m.params()).await,
+                Requests::UpdatePlayer => {
+                    handle_update_player(ctx, m.params(), &req.body, verified).await
+                }
+                Requests::ClaimPlayer => handle_claim_player(ctx, m.params()).await,
                 Requests::GetPlayerGameLeaderboard => {
-                    handle_get_player_game_leaderboard(ctx, m.params()).await
+                    handle_get_player_game_leaderboard(ctx, m.params(), &req.query_string).await
+                }
+                Requests::GetPlayerGameReplay => {
+                    handle_get_player_game_replay(ctx, m.params()).await
                 }
+                Requests::GetPlayerGameFrames => {
+                    handle_get_player_game_frames(ctx, m.params(), &req.query_string).await
+                }
+                Requests::WatchPlayerGame => {
+                    handle_watch_player_game(ctx, m.params(), &req.query_string).await
+                }
+                Requests::GetPlayerGameRace => handle_get_player_game_race(ctx, m.params()).await,
                 Requests::GetGame => handle_get_game(ctx, m.params()).await,
+                Requests::CreateCompetitiveGame => {
+                    handle_create_competitive_game(ctx, &req.body).await
+                }
+                Requests::PostPlayerGameMoves => {
+                    handle_post_player_game_moves(ctx, m.params(), &req.body).await
+                }
+                Requests::GetGlobalLeaderboard => {
+                    handle_get_global_leaderboard(ctx, m.params(), &req.query_string).await
+                }
+                Requests::SyncPlayerGames => {
+                    handle_sync_player_games(ctx, m.params(), &req.body).await
+                }
+                Requests::GetOpenApi => handle_get_openapi(),
+            }
+        }
+        Err(_) => Ok(error_response(
+            ErrorCode::NotFound,
+            &format!("{} not found", route),
+        )),
+    }
+}
+
+/// Implementation of HttpServer trait methods
+#[async_trait]
+impl HttpServer for Twenty48BackendActor {
+    async fn handle_request(
+        &self,
+        ctx: &Context,
+        req: &HttpRequest,
+    ) -> std::result::Result<HttpResponse, RpcError> {
+        middleware::run(ctx, req, || dispatch(ctx, req)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use pwatrip_twenty48_state::{
+        AppendPlayerGameMovesResponse, ClaimPlayerResponse, CreatePlayerGameResponse,
+        GetPlayerGameResponse, GetPlayerResponse, RevealGameResponse, WatchPlayerGameResponse,
+    };
+    use serde::Serialize;
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex};
+    use wasmbus_rpc::{Message, SendOpts, Transport};
+
+    /// An operation with its serialized request.
+    type Call = (String, Vec<u8>);
+
+    /// Stands in for the state provider: each operation is answered with the
+    /// next response queued for it, and the requests made are recorded.
+    #[derive(Clone, Default)]
+    struct StandIn {
+        responses: Arc<Mutex<HashMap<String, VecDeque<Vec<u8>>>>>,
+        calls: Arc<Mutex<Vec<Call>>>,
+    }
+
+    impl StandIn {
+        fn respond<T: Serialize>(&self, operation: &str, response: T) -> &Self {
+            self.responses
+                .lock()
+                .unwrap()
+                .entry(format!("State.{}", operation))
+                .or_default()
+                .push_back(wasmbus_rpc::serialize(&response).unwrap());
+            self
+        }
+
+        fn calls(&self) -> Vec<String> {
+            self.calls
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(op, _)| op.clone())
+                .collect()
+        }
+
+        /// The last request made with the operation.
+        fn request<T: serde::de::DeserializeOwned>(&self, operation: &str) -> T {
+            let calls = self.calls.lock().unwrap();
+            let (_, arg) = calls
+                .iter()
+                .rev()
+                .find(|(op, _)| *op == format!("State.{}", operation))
+                .unwrap();
+            wasmbus_rpc::deserialize(arg).unwrap()
+        }
+    }
+
+    #[async_trait]
+    impl Transport for StandIn {
+        async fn send(
+            &self,
+            _ctx: &Context,
+            req: Message<'_>,
+            _opts: Option<SendOpts>,
+        ) -> RpcResult<Vec<u8>> {
+            self.calls
+                .lock()
+                .unwrap()
+                .push((req.method.to_owned(), req.arg.to_vec()));
+            self.responses
+                .lock()
+                .unwrap()
+                .get_mut(req.method)
+                .and_then(|responses| responses.pop_front())
+                .ok_or_else(|| RpcError::Other(format!("No response for {}", req.method)))
+        }
+
+        fn set_timeout(&self, _interval: std::time::Duration) {}
+    }
+
+    fn error_message(resp: &HttpResponse) -> String {
+        let error: serde_json::Value = serde_json::from_slice(&resp.body).unwrap();
+        error["message"].as_str().unwrap().to_owned()
+    }
+
+    /// A stored competition on the game of `played_game` with the nonce `n`,
+    /// which closed an hour ago or closes in an hour.
+    fn competition(closed: bool, status: &str, commitment: String) -> GetGameResponse {
+        let hour = chrono::Duration::hours(1);
+        let reveal_at = if closed {
+            Utc::now() - hour
+        } else {
+            Utc::now() + hour
+        };
+        GetGameResponse {
+            success: true,
+            seed: Some(42),
+            size: Some(4),
+            competitive: Some(true),
+            commitment: Some(commitment),
+            nonce: Some("n".to_owned()),
+            reveal_at: Some(Timestamp::from(reveal_at)),
+            status: Some(status.to_owned()),
+            ..Default::default()
+        }
+    }
+
+    fn stored_player_game(game: &Game) -> GetPlayerGameResponse {
+        GetPlayerGameResponse {
+            success: true,
+            moves: Some(GameExchange::from_game(game).get_moves_str()),
+            score: Some(game.get_score()),
+            revision: Some("r".to_owned()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn openapi_matches_router() {
+        let router = router();
+        let spec = openapi_spec();
+
+        let mut count = 0;
+        for (path, methods) in spec["paths"].as_object().unwrap() {
+            // Path parameters are filled in, and the route recognized as
+            // the actor would
+            let path = path
+                .split('/')
+                .map(|segment| match segment.starts_with('{') {
+                    true => "x",
+                    false => segment,
+                })
+                .collect::<Vec<_>>()
+                .join("/");
+            for (method, operation) in methods.as_object().unwrap() {
+                let route = method.to_uppercase() + &path;
+                let m = router.recognize(&route).unwrap();
+                assert_eq!(
+                    operation["operationId"],
+                    m.handler().operation().id,
+                    "{}",
+                    route
+                );
+                count += 1;
+            }
+        }
+        assert_eq!(count, routes().len());
+    }
+
+    #[test]
+    fn openapi_schemas_test() {
+        let spec = openapi_spec();
+        let put_player = &spec["paths"]["/players/{player_id}"]["put"]["responses"];
+        assert_eq!(
+            put_player["200"]["content"]["application/json"]["schema"],
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "displayName": { "type": "string", "example": "Alice" },
+                    "lastChange": { "type": "string", "example": "2022-01-28T10:01:00Z" },
+                    "secret": { "type": "string", "example": "0".repeat(64) },
+                },
+                "required": ["displayName", "lastChange"],
+            })
+        );
+        assert_eq!(
+            put_player["401"]["content"]["application/json"]["schema"]["$ref"],
+            "#/components/schemas/Error"
+        );
+        assert_eq!(put_player["400"]["description"], "Error: invalid_request");
+
+        let put_game = &spec["paths"]["/players/{player_id}/games/{game_id}"]["put"]["responses"];
+        for status in [
+            "200", "400", "401", "404", "409", "412", "413", "422", "429", "500",
+        ] {
+            assert!(put_game.get(status).is_some(), "{}", status);
+        }
+        let watch = &spec["paths"]["/players/{player_id}/games/{game_id}/watch"]["get"];
+        assert!(watch["responses"].get("204").is_some());
+
+        let error = &spec["components"]["schemas"]["Error"];
+        assert_eq!(error["required"], serde_json::json!(["code", "message"]));
+        let codes = error["properties"]["code"]["enum"].as_array().unwrap();
+        assert_eq!(codes.len(), ErrorCode::ALL.len());
+        assert!(codes.contains(&serde_json::json!("payload_too_large")));
+
+        // Every schema inferred from the examples has a type
+        fn check(schema: &serde_json::Value, path: &str) {
+            assert!(
+                schema.get("type").is_some() || schema.get("$ref").is_some(),
+                "{}",
+                path
+            );
+            if let Some(items) = schema.get("items") {
+                check(items, &format!("{}[]", path));
+            }
+            for (name, property) in schema["properties"].as_object().into_iter().flatten() {
+                check(property, &format!("{}.{}", path, name));
+            }
+        }
+        for (path, methods) in spec["paths"].as_object().unwrap() {
+            for (method, operation) in methods.as_object().unwrap() {
+                let bodies = operation["responses"]
+                    .as_object()
+                    .unwrap()
+                    .values()
+                    .chain(operation.get("requestBody"));
+                for body in bodies {
+                    for content in body["content"].as_object().into_iter().flatten() {
+                        check(&content.1["schema"], &format!("{} {}", method, path));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn split_route_test() {
+        let (method, path, params) = openapi::split_route(&GET_PLAYER_GAME_ROUTE);
+        assert_eq!(method, "GET");
+        assert_eq!(path, "/players/{player_id}/games/{game_id}");
+        assert_eq!(params, vec![PLAYER_ID_KEY, GAME_ID_KEY]);
+    }
+
+    /// Plays the given number of moves, cycling through the directions.
+    fn played_game(count: usize) -> Game {
+        let mut game = Game::new_from_seed(4, 42, "test");
+        let directions = [
+            Direction::Up,
+            Direction::Left,
+            Direction::Down,
+            Direction::Right,
+        ];
+        for d in directions.iter().cycle().take(100) {
+            if game.get_moves().len() == count {
+                break;
+            }
+            if let Some(g) = game.make_move(d.clone()) {
+                game = g;
+            }
+        }
+        assert_eq!(game.get_moves().len(), count);
+        game
+    }
+
+    #[test]
+    fn replay_frames_test() {
+        let game = played_game(20);
+
+        let frames = replay_frames(&game, 0, MAX_REPLAY_FRAMES);
+        assert_eq!(frames.len(), 21);
+        assert_eq!(frames[0].direction, None);
+        assert_eq!(frames[20].layout, game.layout());
+        assert_eq!(frames[20].score, game.get_score());
+
+        let range = replay_frames(&game, 5, 10);
+        assert_eq!(range.len(), 5);
+        assert_eq!(range[0].index, 5);
+        assert_eq!(range[4].layout, frames[9].layout);
+        assert!(replay_frames(&game, 30, 40).is_empty());
+    }
+
+    #[test]
+    fn frames_range_test() {
+        let ctx = Context::default();
+        let game = played_game(20);
+        let frames = |query: &str| {
+            let stand_in = StandIn::default();
+            stand_in
+                .respond("GetPlayerGame", stored_player_game(&game))
+                .respond(
+                    "GetGame",
+                    GetGameResponse {
+                        success: true,
+                        seed: Some(42),
+                        size: Some(4),
+                        ..Default::default()
+                    },
+                );
+            block_on(get_player_game_frames(
+                &ctx,
+                &StateSender::via(stand_in),
+                "test",
+                "p",
+                query,
+            ))
+            .unwrap()
+        };
+
+        let resp = frames("from=20");
+        assert_eq!(resp.status_code, 200);
+        let replay: comm::ReplayFrames = serde_json::from_slice(&resp.body).unwrap();
+        assert_eq!(replay.frames.len(), 1);
+        assert_eq!(replay.total, 21);
+
+        let resp = frames("from=21");
+        assert_eq!(resp.status_code, 400);
+        assert_eq!(
+            error_message(&resp),
+            "from is greater than the number of moves"
+        );
+
+        let resp = frames(&format!("from={}", usize::MAX));
+        assert_eq!(resp.status_code, 400);
+
+        let resp = frames(&format!("from=5&to={}", usize::MAX));
+        let replay: comm::ReplayFrames = serde_json::from_slice(&resp.body).unwrap();
+        assert_eq!(replay.frames.len(), 16);
+    }
+
+    #[test]
+    fn game_update_test() {
+        let game = played_game(2);
+
+        let update = game_update(&game, "r".to_owned(), 1);
+        assert_eq!(update.from, 1);
+        assert_eq!(update.moves.len(), 1);
+        assert_eq!(update.move_count, 2);
+        assert_eq!(update.score, game.get_score());
+
+        let update = game_update(&game, "r".to_owned(), 5);
+        assert_eq!(update.from, 2);
+        assert!(update.moves.is_empty());
+    }
+
+    #[test]
+    fn watch_test() {
+        let ctx = Context::default();
+        let before = played_game(20);
+        let after = played_game(30);
+        let stored_game = GetGameResponse {
+            success: true,
+            seed: Some(42),
+            size: Some(4),
+            ..Default::default()
+        };
+        let watch = |stand_in: &StandIn, query: &str| {
+            block_on(watch_player_game(
+                &ctx,
+                &StateSender::via(stand_in.clone()),
+                "test",
+                "p",
+                query,
+            ))
+            .unwrap()
+        };
+        let unchanged = || WatchPlayerGameResponse {
+            changed: Some(false),
+            success: true,
+            message: None,
+        };
+
+        // The game changes during the wait
+        let stand_in = StandIn::default();
+        stand_in
+            .respond("GetPlayerGame", stored_player_game(&before))
+            .respond(
+                "GetPlayerGame",
+                GetPlayerGameResponse {
+                    revision: Some("s".to_owned()),
+                    ..stored_player_game(&after)
+                },
+            )
+            .respond("GetGame", stored_game.clone())
+            .respond("GetGame", stored_game.clone());
+        for _ in 0..3 {
+            stand_in.respond("WatchPlayerGame", unchanged());
+        }
+        stand_in.respond(
+            "WatchPlayerGame",
+            WatchPlayerGameResponse {
+                changed: Some(true),
+                ..unchanged()
             },
-            Err(_) => Ok(HttpResponse {
-                status_code: 404,
-                body: format!("{} not found", route).as_bytes().to_vec(),
-                ..Default::default()
-            }),
+        );
+        let resp = watch(&stand_in, "since_revision=r&from=20");
+        assert_eq!(resp.status_code, 200);
+        let update: comm::GameUpdate = serde_json::from_slice(&resp.body).unwrap();
+        assert_eq!(update.revision, "s");
+        assert_eq!(update.from, 20);
+        assert_eq!(update.moves.len(), 10);
+        assert_eq!(update.move_count, 30);
+        let watches = stand_in
+            .calls()
+            .iter()
+            .filter(|c| *c == "State.WatchPlayerGame")
+            .count();
+        assert_eq!(watches, 4);
+        let request: WatchPlayerGameRequest = stand_in.request("WatchPlayerGame");
+        assert_eq!(request.revision, "r");
+        assert!(request.timeout_ms <= WATCH_CALL_TIMEOUT_MS);
+
+        // The game does not change in time
+        let stand_in = StandIn::default();
+        stand_in
+            .respond("GetPlayerGame", stored_player_game(&before))
+            .respond("GetGame", stored_game.clone());
+        for _ in 0..WATCH_TIMEOUT_MS / WATCH_CALL_TIMEOUT_MS {
+            stand_in.respond("WatchPlayerGame", unchanged());
         }
+        let resp = watch(&stand_in, "since_revision=r&from=20");
+        assert_eq!(resp.status_code, 204);
+        assert!(resp.body.is_empty());
+
+        // The watcher is behind, so the game is sent at once
+        let stand_in = StandIn::default();
+        stand_in
+            .respond("GetPlayerGame", stored_player_game(&after))
+            .respond("GetGame", stored_game.clone());
+        let resp = watch(&stand_in, "since_revision=q&from=20");
+        assert_eq!(resp.status_code, 200);
+        assert!(!stand_in
+            .calls()
+            .contains(&"State.WatchPlayerGame".to_owned()));
+
+        let resp = watch(&StandIn::default(), "since_revision=r");
+        assert_eq!(resp.status_code, 400);
+    }
+
+    #[test]
+    fn reveal_if_due_test() {
+        let ctx = Context::default();
+        let commitment = engine::seed_commitment("test", 42, "n");
+
+        // Not due yet
+        let stand_in = StandIn::default();
+        let mut game = game_from_response(competition(false, "Open", commitment.clone())).unwrap();
+        block_on(reveal_if_due(
+            &ctx,
+            &StateSender::via(stand_in.clone()),
+            "test",
+            &mut game,
+        ))
+        .unwrap();
+        assert_eq!(game.status, GameStatus::Open);
+        assert!(stand_in.calls().is_empty());
+
+        // Revealed by this request
+        let stand_in = StandIn::default();
+        stand_in.respond(
+            "RevealGame",
+            RevealGameResponse {
+                success: true,
+                message: None,
+            },
+        );
+        let mut game = game_from_response(competition(true, "Open", commitment.clone())).unwrap();
+        block_on(reveal_if_due(
+            &ctx,
+            &StateSender::via(stand_in.clone()),
+            "test",
+            &mut game,
+        ))
+        .unwrap();
+        assert_eq!(game.status, GameStatus::Revealed);
+        assert_eq!(stand_in.calls(), vec!["State.RevealGame"]);
+
+        // Revealed by another request first
+        let stand_in = StandIn::default();
+        stand_in
+            .respond(
+                "RevealGame",
+                RevealGameResponse {
+                    success: false,
+                    message: None,
+                },
+            )
+            .respond("GetGame", competition(true, "Revealed", commitment.clone()));
+        let mut game = game_from_response(competition(true, "Open", commitment.clone())).unwrap();
+        block_on(reveal_if_due(
+            &ctx,
+            &StateSender::via(stand_in.clone()),
+            "test",
+            &mut game,
+        ))
+        .unwrap();
+        assert_eq!(game.status, GameStatus::Revealed);
+
+        // Not revealed by the database, whose clock is behind
+        let stand_in = StandIn::default();
+        stand_in
+            .respond(
+                "RevealGame",
+                RevealGameResponse {
+                    success: false,
+                    message: None,
+                },
+            )
+            .respond("GetGame", competition(true, "Open", commitment.clone()));
+        let mut game = game_from_response(competition(true, "Open", commitment)).unwrap();
+        block_on(reveal_if_due(
+            &ctx,
+            &StateSender::via(stand_in),
+            "test",
+            &mut game,
+        ))
+        .unwrap();
+        assert_eq!(game.status, GameStatus::Open);
+    }
+
+    #[test]
+    fn competition_commitment_test() {
+        let ctx = Context::default();
+        let game = played_game(10);
+
+        let load = |stored: GetGameResponse| {
+            let stand_in = StandIn::default();
+            stand_in
+                .respond("GetPlayerGame", stored_player_game(&game))
+                .respond("GetGame", stored);
+            block_on(load_player_game(
+                &ctx,
+                &StateSender::via(stand_in),
+                "test",
+                "p",
+            ))
+            .unwrap()
+        };
+
+        let commitment = engine::seed_commitment("test", 42, "n");
+        let loaded = load(competition(true, "Revealed", commitment.clone())).unwrap();
+        assert!(!loaded.seed_secret);
+        assert!(Game::try_from(&loaded.gx).unwrap() == game);
+
+        let resp = load(competition(false, "Open", commitment)).err().unwrap();
+        assert_eq!(resp.status_code, 403);
+
+        for commitment in [
+            engine::seed_commitment("test", 43, "n"),
+            engine::seed_commitment("test", 42, "m"),
+        ] {
+            let resp = load(competition(true, "Revealed", commitment))
+                .err()
+                .unwrap();
+            assert_eq!(resp.status_code, 500);
+            assert_eq!(
+                error_message(&resp),
+                "Stored game does not match the seed commitment"
+            );
+        }
+    }
+
+    #[test]
+    fn competition_moves_test() {
+        let ctx = Context::default();
+        let commitment = engine::seed_commitment("test", 42, "n");
+        let batch = comm::MoveBatch {
+            moves: vec![Direction::Up],
+        };
+
+        let stand_in = StandIn::default();
+        stand_in.respond("GetGame", competition(true, "Open", commitment.clone()));
+        let resp = block_on(post_player_game_moves(
+            &ctx,
+            &StateSender::via(stand_in),
+            "test",
+            "p",
+            &batch,
+        ))
+        .unwrap();
+        assert_eq!(resp.status_code, 403);
+        assert_eq!(error_message(&resp), "Competition is closed");
+
+        let stand_in = StandIn::default();
+        stand_in
+            .respond("GetGame", competition(false, "Open", commitment))
+            .respond("GetPlayerGame", GetPlayerGameResponse::default())
+            .respond(
+                "CreatePlayerGame",
+                CreatePlayerGameResponse {
+                    success: true,
+                    ..Default::default()
+                },
+            );
+        let resp = block_on(post_player_game_moves(
+            &ctx,
+            &StateSender::via(stand_in),
+            "test",
+            "p",
+            &batch,
+        ))
+        .unwrap();
+        let result: comm::MoveBatchResult = serde_json::from_slice(&resp.body).unwrap();
+        assert_eq!(result.move_count, 1);
+        assert_eq!(
+            result.signature,
+            Some(engine::spawn_signature(42, "test", "p", 0, &result.spawns))
+        );
+    }
+
+    #[test]
+    fn append_moves_test() {
+        let ctx = Context::default();
+        let before = played_game(20);
+        let after = played_game(30);
+        let delta = comm::MoveDelta {
+            revision: "r".to_owned(),
+            moves: after.get_moves()[20..]
+                .iter()
+                .map(|c| Direction::from_code(*c).unwrap())
+                .collect(),
+        };
+        let stored_game = GetGameResponse {
+            success: true,
+            seed: Some(42),
+            size: Some(4),
+            ..Default::default()
+        };
+
+        // Played from the stored snapshot, or from the replayed game for games
+        // stored without one
+        let snapshots = [
+            Some(serde_json::to_string(&before.snapshot()).unwrap()),
+            None,
+        ];
+        for snapshot in snapshots {
+            let stand_in = StandIn::default();
+            stand_in
+                .respond("GetGame", stored_game.clone())
+                .respond(
+                    "GetPlayerGame",
+                    GetPlayerGameResponse {
+                        snapshot,
+                        ..stored_player_game(&before)
+                    },
+                )
+                .respond(
+                    "AppendPlayerGameMoves",
+                    AppendPlayerGameMovesResponse {
+                        success: true,
+                        revision: Some("s".to_owned()),
+                        message: None,
+                    },
+                );
+            let resp = block_on(append_player_game_moves(
+                &ctx,
+                &StateSender::via(stand_in.clone()),
+                "test",
+                "p",
+                &delta,
+            ))
+            .unwrap();
+            let result: comm::MoveDeltaResult = serde_json::from_slice(&resp.body).unwrap();
+            assert_eq!(result.score, after.get_score());
+            assert_eq!(result.move_count, 30);
+
+            let request: AppendPlayerGameMovesRequest = stand_in.request("AppendPlayerGameMoves");
+            let snapshot: Snapshot = serde_json::from_str(&request.snapshot.unwrap()).unwrap();
+            assert_eq!(snapshot, after.snapshot());
+        }
+    }
+
+    #[test]
+    fn remote_address_test() {
+        let request = |forwarded: Option<&str>| {
+            let mut req = HttpRequest::default();
+            if let Some(forwarded) = forwarded {
+                req.header
+                    .insert("X-Forwarded-For".to_owned(), vec![forwarded.to_owned()]);
+            }
+            req
+        };
+        assert_eq!(
+            remote_address(&request(Some("10.0.0.1, 192.168.0.1"))),
+            "192.168.0.1"
+        );
+        assert_eq!(remote_address(&request(Some("10.0.0.1"))), "10.0.0.1");
+        assert_eq!(remote_address(&request(Some("10.0.0.1, "))), "unknown");
+        assert_eq!(remote_address(&request(None)), "unknown");
+    }
+
+    #[test]
+    fn claim_player_test() {
+        let ctx = Context::default();
+        let stored = |secret: Option<&str>| GetPlayerResponse {
+            success: true,
+            display_name: Some("Player".to_owned()),
+            last_change: Some(Timestamp::from(Utc::now())),
+            secret: secret.map(str::to_owned),
+            ..Default::default()
+        };
+        let claim = |stand_in: &StandIn| {
+            block_on(claim_player(
+                &ctx,
+                &StateSender::via(stand_in.clone()),
+                "player",
+                "secret".to_owned(),
+            ))
+            .unwrap()
+        };
+
+        let stand_in = StandIn::default();
+        stand_in.respond("GetPlayer", stored(None)).respond(
+            "ClaimPlayer",
+            ClaimPlayerResponse {
+                success: true,
+                message: None,
+            },
+        );
+        let resp = claim(&stand_in);
+        assert_eq!(resp.status_code, 200);
+        let player: comm::Player = serde_json::from_slice(&resp.body).unwrap();
+        assert_eq!(player.secret.as_deref(), Some("secret"));
+        let request: ClaimPlayerRequest = stand_in.request("ClaimPlayer");
+        assert_eq!(request.player_id, "player");
+        assert_eq!(request.secret, "secret");
+
+        // Claimed before it was read
+        let stand_in = StandIn::default();
+        stand_in.respond("GetPlayer", stored(Some("other")));
+        let resp = claim(&stand_in);
+        assert_eq!(resp.status_code, 409);
+        assert_eq!(stand_in.calls(), vec!["State.GetPlayer"]);
+
+        // Claimed by another request since it was read
+        let stand_in = StandIn::default();
+        stand_in.respond("GetPlayer", stored(None)).respond(
+            "ClaimPlayer",
+            ClaimPlayerResponse {
+                success: false,
+                message: None,
+            },
+        );
+        assert_eq!(claim(&stand_in).status_code, 409);
+
+        let stand_in = StandIn::default();
+        stand_in.respond(
+            "GetPlayer",
+            GetPlayerResponse {
+                success: false,
+                message: Some("Player not found".to_owned()),
+                ..Default::default()
+            },
+        );
+        assert_eq!(claim(&stand_in).status_code, 404);
+    }
+
+    #[test]
+    fn secret_seed_test() {
+        let game = played_game(10);
+        let mut loaded = LoadedGame {
+            gx: GameExchange::from_game(&game),
+            revision: "r".to_owned(),
+            seed_secret: true,
+        };
+
+        let value: serde_json::Value = serde_json::from_slice(&loaded.to_json()).unwrap();
+        assert!(value.get("seed").is_none());
+        assert_eq!(value["moves"].as_array().unwrap().len(), 10);
+        let spawns: Vec<Spawn> = serde_json::from_value(value["spawns"].clone()).unwrap();
+        assert_eq!(spawns, game.spawns());
+
+        loaded.seed_secret = false;
+        let value: serde_json::Value = serde_json::from_slice(&loaded.to_json()).unwrap();
+        assert_eq!(value["seed"], "42");
+        assert!(value.get("spawns").is_none());
     }
 }
@@ -0,0 +1,317 @@
+//! Cross-cutting handling of every request, around the routed handlers.
+//! Each middleware can answer a request before it is routed, and can change
+//! every response on its way out.
+
+use crate::{error_response, header, ErrorCode, HttpResponseCodes};
+use chrono::{DateTime, Utc};
+use flate2::{write::GzEncoder, Compression};
+use std::io::Write;
+use wasmbus_rpc::actor::prelude::*;
+use wasmcloud_interface_httpserver::{HttpRequest, HttpResponse};
+
+/// Request bodies are limited to 1 MiB, which holds games of over 100000
+/// moves.
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+/// Responses smaller than this are not worth compressing.
+const MIN_GZIP_BYTES: usize = 1024;
+/// Request IDs sent by clients are used if they are at most this long.
+const MAX_REQUEST_ID_LEN: usize = 64;
+
+/// A request on its way through the middleware, with the values they attach
+/// to it.
+pub struct Exchange<'a> {
+    pub req: &'a HttpRequest,
+    pub request_id: String,
+    pub started: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait Middleware: Sync {
+    /// Runs before the request is routed. Returning a response skips the
+    /// later middleware and the handler.
+    async fn before(
+        &self,
+        _ctx: &Context,
+        _exchange: &mut Exchange<'_>,
+    ) -> RpcResult<Option<HttpResponse>> {
+        Ok(None)
+    }
+
+    /// Runs on the response, in the reverse order of `before`, for every
+    /// middleware whose `before` ran.
+    fn after(&self, _exchange: &Exchange<'_>, _resp: &mut HttpResponse) {}
+}
+
+/// The middleware of the actor, outermost first.
+pub static MIDDLEWARE: &[&dyn Middleware] = &[&RequestId, &AccessLog, &Cors, &BodyLimit, &Gzip];
+
+/// Runs a request through the middleware and the given handler.
+pub async fn run<F, Fut>(ctx: &Context, req: &HttpRequest, handler: F) -> RpcResult<HttpResponse>
+where
+    F: FnOnce() -> Fut,
+    Fut: std::future::Future<Output = RpcResult<HttpResponse>>,
+{
+    let mut exchange = Exchange {
+        req,
+        request_id: String::new(),
+        started: Utc::now(),
+    };
+
+    let mut ran = 0;
+    let mut early = None;
+    for middleware in MIDDLEWARE {
+        ran += 1;
+        if let Some(resp) = middleware.before(ctx, &mut exchange).await? {
+            early = Some(resp);
+            break;
+        }
+    }
+
+    let mut resp = match early {
+        Some(resp) => resp,
+        None => handler().await.or_else(crate::rpc_error_to_http_response)?,
+    };
+
+    for middleware in MIDDLEWARE[..ran].iter().rev() {
+        middleware.after(&exchange, &mut resp);
+    }
+    Ok(resp)
+}
+
+/// Tags each request and its response with an `X-Request-Id`, taken from the
+/// request if the client or a proxy set a usable one.
+pub struct RequestId;
+
+#[async_trait]
+impl Middleware for RequestId {
+    async fn before(
+        &self,
+        _ctx: &Context,
+        exchange: &mut Exchange<'_>,
+    ) -> RpcResult<Option<HttpResponse>> {
+        exchange.request_id = match header(exchange.req, "X-Request-Id") {
+            Some(id)
+                if !id.is_empty()
+                    && id.len() <= MAX_REQUEST_ID_LEN
+                    && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') =>
+            {
+                id.to_owned()
+            }
+            _ => wasmcloud_interface_numbergen::generate_guid().await?,
+        };
+        Ok(None)
+    }
+
+    fn after(&self, exchange: &Exchange<'_>, resp: &mut HttpResponse) {
+        resp.header
+            .insert("X-Request-Id".to_owned(), vec![exchange.request_id.clone()]);
+    }
+}
+
+/// Logs one JSON line per request.
+pub struct AccessLog;
+
+#[async_trait]
+impl Middleware for AccessLog {
+    fn after(&self, exchange: &Exchange<'_>, resp: &mut HttpResponse) {
+        let entry = serde_json::json!({
+            "requestId": exchange.request_id,
+            "method": exchange.req.method,
+            "path": exchange.req.path,
+            "status": resp.status_code,
+            "bytes": resp.body.len(),
+            "durationMs": (Utc::now() - exchange.started).num_milliseconds(),
+        });
+        console_log(&entry.to_string());
+    }
+}
+
+/// Allows the API to be called from any origin. Requests are authenticated
+/// with bearer tokens rather than cookies, so other origins gain nothing
+/// they could not do without a browser.
+pub struct Cors;
+
+#[async_trait]
+impl Middleware for Cors {
+    async fn before(
+        &self,
+        _ctx: &Context,
+        exchange: &mut Exchange<'_>,
+    ) -> RpcResult<Option<HttpResponse>> {
+        if exchange.req.method != "OPTIONS" {
+            return Ok(None);
+        }
+        let mut resp = HttpResponse {
+            status_code: HttpResponseCodes::NoContent as u16,
+            ..Default::default()
+        };
+        for (name, value) in [
+            ("Access-Control-Allow-Methods", "GET, PUT, PATCH, POST"),
+            (
+                "Access-Control-Allow-Headers",
+                "Authorization, Content-Type, If-Match, X-Request-Id",
+            ),
+            ("Access-Control-Max-Age", "86400"),
+        ] {
+            resp.header.insert(name.to_owned(), vec![value.to_owned()]);
+        }
+        Ok(Some(resp))
+    }
+
+    fn after(&self, _exchange: &Exchange<'_>, resp: &mut HttpResponse) {
+        resp.header.insert(
+            "Access-Control-Allow-Origin".to_owned(),
+            vec!["*".to_owned()],
+        );
+        resp.header.insert(
+            "Access-Control-Expose-Headers".to_owned(),
+            vec!["ETag, Location, Retry-After, X-Request-Id".to_owned()],
+        );
+    }
+}
+
+/// Rejects request bodies over `MAX_BODY_BYTES` before they are parsed.
+pub struct BodyLimit;
+
+#[async_trait]
+impl Middleware for BodyLimit {
+    async fn before(
+        &self,
+        _ctx: &Context,
+        exchange: &mut Exchange<'_>,
+    ) -> RpcResult<Option<HttpResponse>> {
+        match exchange.req.body.len() > MAX_BODY_BYTES {
+            true => Ok(Some(error_response(
+                ErrorCode::PayloadTooLarge,
+                &format!("Request bodies are limited to {} bytes", MAX_BODY_BYTES),
+            ))),
+            false => Ok(None),
+        }
+    }
+}
+
+/// Compresses responses for clients which accept gzip.
+pub struct Gzip;
+
+/// Whether an `Accept-Encoding` header accepts gzip, by naming it or with
+/// `*`. Codings with a quality of 0 are not acceptable.
+fn accepts_gzip(accept_encoding: &str) -> bool {
+    let mut wildcard = false;
+    for entry in accept_encoding.split(',') {
+        let mut parts = entry.split(';');
+        let coding = parts.next().unwrap_or_default().trim();
+        let acceptable = parts
+            .find_map(|p| p.trim().strip_prefix("q="))
+            .is_none_or(|q| q.trim().parse::<f32>().is_ok_and(|q| q > 0.0));
+        if coding.eq_ignore_ascii_case("gzip") {
+            return acceptable;
+        }
+        if coding == "*" {
+            wildcard = acceptable;
+        }
+    }
+    wildcard
+}
+
+#[async_trait]
+impl Middleware for Gzip {
+    fn after(&self, exchange: &Exchange<'_>, resp: &mut HttpResponse) {
+        let accepted = header(exchange.req, "Accept-Encoding").is_some_and(accepts_gzip);
+        if !accepted
+            || resp.body.len() < MIN_GZIP_BYTES
+            || resp.header.contains_key("Content-Encoding")
+        {
+            return;
+        }
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        if let Ok(body) = encoder.write_all(&resp.body).and_then(|_| encoder.finish()) {
+            resp.body = body;
+            resp.header
+                .insert("Content-Encoding".to_owned(), vec!["gzip".to_owned()]);
+            resp.header
+                .insert("Vary".to_owned(), vec!["Accept-Encoding".to_owned()]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    fn exchange(req: &HttpRequest) -> Exchange<'_> {
+        Exchange {
+            req,
+            request_id: "test".to_owned(),
+            started: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn gzip_test() {
+        let mut req = HttpRequest::default();
+        let body = "2048".repeat(1000).into_bytes();
+        let mut resp = HttpResponse {
+            body: body.clone(),
+            ..Default::default()
+        };
+        Gzip.after(&exchange(&req), &mut resp);
+        assert_eq!(resp.body, body);
+
+        req.header.insert(
+            "Accept-Encoding".to_owned(),
+            vec!["deflate, gzip".to_owned()],
+        );
+        Gzip.after(&exchange(&req), &mut resp);
+        assert_eq!(resp.header["Content-Encoding"], vec!["gzip"]);
+        let mut decompressed = vec![];
+        GzDecoder::new(&resp.body[..])
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, body);
+    }
+
+    #[test]
+    fn accept_encoding_test() {
+        assert!(accepts_gzip("gzip"));
+        assert!(accepts_gzip("deflate, GZIP;q=0.5"));
+        assert!(accepts_gzip("br, *"));
+        assert!(!accepts_gzip("gzip;q=0"));
+        assert!(!accepts_gzip("gzip; q=0.0, deflate"));
+        assert!(!accepts_gzip("*, gzip;q=0"));
+        assert!(!accepts_gzip("*;q=0"));
+        assert!(!accepts_gzip("gzipx, deflate"));
+        assert!(!accepts_gzip(""));
+    }
+
+    #[test]
+    fn preflight_test() {
+        let req = HttpRequest {
+            method: "OPTIONS".to_owned(),
+            path: "/players/x".to_owned(),
+            ..Default::default()
+        };
+        let mut exchange = exchange(&req);
+        let resp = futures::executor::block_on(Cors.before(&Context::default(), &mut exchange))
+            .unwrap()
+            .unwrap();
+        assert_eq!(resp.status_code, 204);
+        assert!(resp.header.contains_key("Access-Control-Allow-Headers"));
+    }
+
+    #[test]
+    fn body_limit_test() {
+        let req = HttpRequest {
+            body: vec![0; MAX_BODY_BYTES + 1],
+            ..Default::default()
+        };
+        let mut exchange = exchange(&req);
+        let resp =
+            futures::executor::block_on(BodyLimit.before(&Context::default(), &mut exchange))
+                .unwrap()
+                .unwrap();
+        assert_eq!(resp.status_code, 413);
+    }
+}
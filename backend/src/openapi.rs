@@ -0,0 +1,252 @@
+//! OpenAPI description of the backend API, served at `GET /openapi.json`.
+//! The schemas are inferred from example values of the payload types, so
+//! that they follow the field names given by their serde attributes.
+
+use crate::comm::ErrorCode;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+
+/// The body of a request or response.
+pub enum Body {
+    /// A JSON body, described by example values of its type.
+    Json(Vec<Value>),
+    /// A binary body with the given content type.
+    Binary(&'static str),
+}
+
+/// Description of the operation behind a route.
+pub struct Operation {
+    pub id: &'static str,
+    pub summary: &'static str,
+    /// Optional query parameters, all of them strings or integers.
+    pub query: &'static [&'static str],
+    /// Whether the route takes a bearer token of the player in the path.
+    pub authenticated: bool,
+    pub request: Option<Body>,
+    pub status: u16,
+    pub response: Body,
+    /// Whether the route also responds with `204` and no body.
+    pub no_content: bool,
+    /// The errors the route responds with, each with the status given to
+    /// `spec`.
+    pub errors: Vec<ErrorCode>,
+}
+
+/// Serializes an example value of a payload type.
+pub fn example<T: Serialize>(value: &T) -> Vec<Value> {
+    examples(&[value])
+}
+
+/// Serializes example values of a payload type. Fields which are missing or
+/// null in any of them are optional, so each optional field should be left
+/// out of one example and given a value in another.
+pub fn examples<T: Serialize>(values: &[T]) -> Vec<Value> {
+    values
+        .iter()
+        .map(|value| serde_json::to_value(value).unwrap())
+        .collect()
+}
+
+/// Infers a JSON schema from example values of a type. Fields given in every
+/// example and not null in any are required, and the items of arrays are
+/// described by the items of all examples. Values which are only ever null
+/// have no type, so examples should give a value for each field and array.
+pub fn schema_of(examples: &[Value]) -> Value {
+    let values: Vec<&Value> = examples.iter().filter(|v| !v.is_null()).collect();
+    let mut schema = match values.first() {
+        None | Some(Value::Null) => json!({}),
+        Some(Value::Bool(_)) => json!({ "type": "boolean" }),
+        Some(Value::Number(_)) if values.iter().any(|v| v.is_f64()) => {
+            json!({ "type": "number" })
+        }
+        Some(Value::Number(_)) => json!({ "type": "integer" }),
+        Some(Value::String(s)) => json!({ "type": "string", "example": s }),
+        Some(Value::Array(_)) => {
+            let items: Vec<Value> = values
+                .iter()
+                .filter_map(|v| v.as_array())
+                .flatten()
+                .cloned()
+                .collect();
+            json!({ "type": "array", "items": schema_of(&items) })
+        }
+        Some(Value::Object(_)) => {
+            let objects: Vec<&Map<String, Value>> =
+                values.iter().filter_map(|v| v.as_object()).collect();
+            let mut properties = Map::new();
+            let mut required = vec![];
+            for name in objects.iter().flat_map(|o| o.keys()) {
+                if properties.contains_key(name) {
+                    continue;
+                }
+                if objects
+                    .iter()
+                    .all(|o| o.get(name).is_some_and(|f| !f.is_null()))
+                {
+                    required.push(name.clone());
+                }
+                // Missing fields are optional rather than null
+                let given: Vec<Value> = objects
+                    .iter()
+                    .filter_map(|o| o.get(name))
+                    .cloned()
+                    .collect();
+                properties.insert(name.clone(), schema_of(&given));
+            }
+            let mut schema = json!({ "type": "object", "properties": properties });
+            if !required.is_empty() {
+                schema["required"] = json!(required);
+            }
+            schema
+        }
+    };
+    if values.len() < examples.len() {
+        schema["nullable"] = json!(true);
+    }
+    schema
+}
+
+/// The schema of the body of error responses.
+fn error_schema() -> Value {
+    let codes: Vec<Value> = ErrorCode::ALL.iter().map(|code| json!(code)).collect();
+    json!({
+        "type": "object",
+        "properties": {
+            "code": { "type": "string", "enum": codes },
+            "message": { "type": "string" },
+        },
+        "required": ["code", "message"],
+    })
+}
+
+fn content(body: &Body) -> Value {
+    match body {
+        Body::Json(examples) => json!({
+            "application/json": { "schema": schema_of(examples) }
+        }),
+        Body::Binary(content_type) => json!({
+            content_type.to_string(): { "schema": { "type": "string", "format": "binary" } }
+        }),
+    }
+}
+
+fn parameter(name: &str, location: &str, required: bool) -> Value {
+    json!({
+        "name": name,
+        "in": location,
+        "required": required,
+        "schema": { "type": "string" },
+    })
+}
+
+/// Splits a route of the router, such as `GET/players/:player_id`, into its
+/// method and its OpenAPI path, such as `/players/{player_id}`, with the
+/// names of the path parameters.
+pub fn split_route(route: &str) -> (&str, String, Vec<&str>) {
+    let (method, path) = route.split_at(route.find('/').unwrap_or(route.len()));
+    let mut params = vec![];
+    let path = path
+        .split('/')
+        .map(|segment| match segment.strip_prefix(':') {
+            Some(name) => {
+                params.push(name);
+                format!("{{{}}}", name)
+            }
+            None => segment.to_owned(),
+        })
+        .collect::<Vec<_>>()
+        .join("/");
+    (method, path, params)
+}
+
+/// Builds the OpenAPI document of the given routes, where errors are sent
+/// with the status of their code given by `status`.
+pub fn spec(routes: &[(&str, Operation)], status: fn(ErrorCode) -> u16) -> Value {
+    let mut paths = Map::new();
+    for (route, operation) in routes {
+        let (method, path, path_params) = split_route(route);
+
+        let parameters: Vec<Value> = path_params
+            .iter()
+            .map(|name| parameter(name, "path", true))
+            .chain(
+                operation
+                    .query
+                    .iter()
+                    .map(|name| parameter(name, "query", false)),
+            )
+            .collect();
+
+        let mut responses = Map::new();
+        responses.insert(
+            operation.status.to_string(),
+            json!({ "description": "Success", "content": content(&operation.response) }),
+        );
+        if operation.no_content {
+            responses.insert("204".to_owned(), json!({ "description": "No content" }));
+        }
+        // Codes with the same status share a response
+        let mut errors: BTreeMap<u16, Vec<Value>> = BTreeMap::new();
+        for code in &operation.errors {
+            let codes = errors.entry(status(*code)).or_default();
+            if !codes.contains(&json!(code)) {
+                codes.push(json!(code));
+            }
+        }
+        for (status, codes) in errors {
+            let codes: Vec<&str> = codes.iter().filter_map(|c| c.as_str()).collect();
+            responses.insert(
+                status.to_string(),
+                json!({
+                    "description": format!("Error: {}", codes.join(", ")),
+                    "content": {
+                        "application/json": {
+                            "schema": { "$ref": "#/components/schemas/Error" }
+                        }
+                    },
+                }),
+            );
+        }
+
+        let mut value = json!({
+            "operationId": operation.id,
+            "summary": operation.summary,
+            "parameters": parameters,
+            "responses": responses,
+        });
+        if let Some(body) = &operation.request {
+            value["requestBody"] = json!({ "required": true, "content": content(body) });
+        }
+        if operation.authenticated {
+            value["security"] = json!([{ "bearer": [] }]);
+        }
+
+        paths
+            .entry(path)
+            .or_insert_with(|| json!({}))
+            .as_object_mut()
+            .unwrap()
+            .insert(method.to_lowercase(), value);
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "twenty48",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths,
+        "components": {
+            "schemas": { "Error": error_schema() },
+            "securitySchemes": {
+                "bearer": {
+                    "type": "http",
+                    "scheme": "bearer",
+                    "description": "Token created with `engine::player_token` from the \
+                        secret of the player",
+                },
+            },
+        },
+    })
+}
@@ -0,0 +1,133 @@
+//! Estimates the best attainable score for a seed with a beam search.
+//!
+//! Usage: solver --seed <seed> [--size <size>] [--id <game id>]
+//!        [--topology Classic|Wraparound] [--blockers <cell,cell,...>]
+//!        [--width <beam width>] [--max-moves <moves>]
+//!        [--checkpoint <file>] [--every <moves>]
+//!
+//! With `--checkpoint` the search state is saved to the file every
+//! `--every` moves, and a search is resumed from the file if it exists. The
+//! game parameters are then taken from the checkpoint. The best game found
+//! is printed in the exchange format.
+
+use engine::solver::{BeamSearch, Checkpoint};
+use engine::{Game, GameExchange, Topology};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+struct Options {
+    seed: Option<u64>,
+    size: usize,
+    id: String,
+    topology: Topology,
+    blockers: Vec<usize>,
+    width: usize,
+    max_moves: usize,
+    checkpoint: Option<String>,
+    every: usize,
+}
+
+fn parse_options() -> Result<Options, String> {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if args.len() % 2 != 0 {
+        return Err("Expected pairs of options and values".into());
+    }
+    let mut values = args
+        .chunks(2)
+        .map(|kv| (kv[0].clone(), kv[1].clone()))
+        .collect::<HashMap<_, _>>();
+    let mut take = |key: &str| values.remove(key);
+    fn parse<T: std::str::FromStr>(
+        key: &str,
+        value: Option<String>,
+        default: Option<T>,
+    ) -> Result<T, String> {
+        match value {
+            Some(v) => v.parse().map_err(|_| format!("Invalid value for {}", key)),
+            None => default.ok_or_else(|| format!("Missing {}", key)),
+        }
+    }
+
+    let options = Options {
+        seed: take("--seed")
+            .map(|s| parse("--seed", Some(s), None))
+            .transpose()?,
+        size: parse("--size", take("--size"), Some(4))?,
+        id: take("--id").unwrap_or_default(),
+        topology: parse("--topology", take("--topology"), Some(Topology::Classic))?,
+        blockers: match take("--blockers") {
+            Some(b) => b
+                .split(',')
+                .map(|c| c.trim().parse())
+                .collect::<Result<_, _>>()
+                .map_err(|_| "Invalid value for --blockers".to_owned())?,
+            None => vec![],
+        },
+        width: parse("--width", take("--width"), Some(256))?,
+        max_moves: parse("--max-moves", take("--max-moves"), Some(usize::MAX))?,
+        checkpoint: take("--checkpoint"),
+        every: parse("--every", take("--every"), Some(100))?,
+    };
+    if let Some(key) = values.keys().next() {
+        return Err(format!("Unknown option {}", key));
+    }
+    Ok(options)
+}
+
+fn load_checkpoint(path: &str) -> Result<BeamSearch, String> {
+    let json = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let checkpoint: Checkpoint = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+    BeamSearch::from_checkpoint(&checkpoint).ok_or_else(|| "Invalid checkpoint".to_owned())
+}
+
+fn save_checkpoint(path: &str, search: &BeamSearch) -> Result<(), String> {
+    // Write to a temporary file first, so that an interrupted save does not
+    // destroy the previous checkpoint
+    let json = serde_json::to_string(&search.checkpoint()).map_err(|e| e.to_string())?;
+    let tmp = format!("{}.tmp", path);
+    fs::write(&tmp, json).map_err(|e| e.to_string())?;
+    fs::rename(&tmp, path).map_err(|e| e.to_string())
+}
+
+fn run(options: Options) -> Result<(), String> {
+    let mut search = match &options.checkpoint {
+        Some(path) if Path::new(path).exists() => load_checkpoint(path)?,
+        _ => {
+            let game = Game::new_from_definition(
+                options.size,
+                options.seed.ok_or("Missing --seed")?,
+                &options.id,
+                options.topology,
+                options.blockers.clone(),
+            )
+            .ok_or("Invalid game definition")?;
+            BeamSearch::new(game, options.width)
+        }
+    };
+
+    while !search.is_done() && search.depth() < options.max_moves {
+        let target = (search.depth() + options.every.max(1)).min(options.max_moves);
+        search.run(target);
+        if let Some(path) = &options.checkpoint {
+            save_checkpoint(path, &search)?;
+        }
+        eprintln!(
+            "moves: {}, best score: {}",
+            search.depth(),
+            search.best().get_score()
+        );
+    }
+
+    let gx = GameExchange::from_game(search.best());
+    println!("{}", gx.to_json().ok_or("Could not serialize game")?);
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = parse_options().and_then(run) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
@@ -1,10 +1,28 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+mod digest;
+mod race;
+mod replay;
+mod snapshot;
+pub mod solver;
+
+use alloc::borrow::ToOwned;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::fmt;
+use core::str::FromStr;
 #[cfg(feature = "random")]
 use nanorand::{Rng, WyRand};
 use oorandom::Rand64;
-use serde;
+pub use race::{GhostRace, TileRace};
+pub use replay::Replay;
 use serde::{Deserialize, Serialize};
-use serde_json;
-use std::convert::TryFrom;
+pub use snapshot::Snapshot;
 #[cfg(feature = "random")]
 use uuid::Uuid;
 #[cfg(feature = "bindgen")]
@@ -20,8 +38,13 @@ static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct Tile {
     pub id: usize,
-    pub value: usize,
+    /// The tile value as a power of two, so that a tile with exponent 11
+    /// has the value 2048.
+    pub exponent: u8,
     pub merged_with: Option<usize>,
+    /// Blockers are immovable cells which tiles can neither pass nor merge
+    /// with. The exponent of a blocker is 0.
+    pub blocker: bool,
 }
 
 #[cfg_attr(feature = "bindgen", wasm_bindgen)]
@@ -29,9 +52,13 @@ pub struct Tile {
 pub struct GameExchange {
     player: String,
     id: String,
-    score: usize,
+    score: u64,
     seed: String,
     size: usize,
+    #[serde(default)]
+    topology: Topology,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    blockers: Vec<usize>,
     moves: Vec<Direction>,
 }
 
@@ -39,11 +66,12 @@ pub struct GameExchange {
 #[derive(Debug, Clone, PartialEq)]
 pub struct Game {
     id: String,
-    score: usize,
+    score: u64,
     game_over: bool,
     seed: u64,
     rng: Rand64,
     size: usize,
+    topology: Topology,
     next_tile_id: usize,
     tiles: Vec<Option<Tile>>,
     moves: Vec<Direction>,
@@ -58,6 +86,59 @@ pub enum Direction {
     Down,
 }
 
+/// The shape of the board. On a wraparound board each line is a ring: tiles
+/// slide as on the classic board, and the tiles at the two ends of a line are
+/// neighbours across the edge, so they can merge.
+#[cfg_attr(feature = "bindgen", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Topology {
+    #[default]
+    Classic,
+    Wraparound,
+}
+
+/// One of the eight rotations and reflections of a square board.
+#[cfg_attr(feature = "bindgen", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Symmetry {
+    Identity,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    FlipHorizontal,
+    FlipVertical,
+    Transpose,
+    AntiTranspose,
+}
+
+/// A tile placed on the board after a move, for games whose tiles are
+/// spawned by a server instead of the local random number generator.
+#[cfg_attr(feature = "bindgen", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Spawn {
+    /// Row-major cell index.
+    pub index: u32,
+    pub exponent: u8,
+}
+
+/// The outcome of `Game::play`, so that callers can update the board
+/// without comparing games or parsing JSON.
+#[cfg_attr(feature = "bindgen", wasm_bindgen)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct MoveResult {
+    game: Option<Game>,
+    /// Points scored by the merges of this move.
+    pub score_gained: u64,
+    /// Number of merges made by this move.
+    pub merges: u32,
+    /// Cell index of the spawned tile, if any.
+    pub spawned: Option<u32>,
+}
+
+/// Marks a cell without a tile in the arrays returned by `Game::tile_ids`
+/// and `Game::merged_ids`.
+pub const NO_TILE: u32 = u32::MAX;
+
 #[derive(Clone)]
 struct Cursor {
     row: usize,
@@ -67,14 +148,57 @@ struct Cursor {
     next_across_f: fn(&Self) -> Option<Self>,
 }
 
+/// Commitment to the seed of a game, which can be published before the seed
+/// is revealed. It is the hex encoded SHA-256 of `<game id>:<seed>:<nonce>`,
+/// where the nonce is a random string kept secret and revealed with the seed,
+/// so that the seed cannot be found by hashing candidate seeds.
+#[cfg_attr(feature = "bindgen", wasm_bindgen)]
+pub fn seed_commitment(game_id: &str, seed: u64, nonce: &str) -> String {
+    digest::to_hex(&digest::sha256(
+        format!("{}:{}:{}", game_id, seed, nonce).as_bytes(),
+    ))
+}
+
+/// Signature of the tiles spawned by a server after the moves of a game from
+/// the index `from` on. It is keyed with the seed, so that once the seed of a
+/// competition is revealed a player can show which tiles they were sent. The
+/// signature is the hex encoded HMAC-SHA-256 of
+/// `<game id>:<player id>:<from>:<spawns>`, keyed with the decimal seed, where
+/// the spawns are given as `<index>,<exponent>` and separated by `;`.
+pub fn spawn_signature(
+    seed: u64,
+    game_id: &str,
+    player_id: &str,
+    from: usize,
+    spawns: &[Spawn],
+) -> String {
+    let spawns = spawns
+        .iter()
+        .map(|s| format!("{},{}", s.index, s.exponent))
+        .collect::<Vec<_>>()
+        .join(";");
+    let message = format!("{}:{}:{}:{}", game_id, player_id, from, spawns);
+    let signature = digest::hmac_sha256(seed.to_string().as_bytes(), message.as_bytes());
+    digest::to_hex(&signature)
+}
+
+/// Bearer token which authorizes writes for a player until `expires`, in
+/// seconds since the Unix epoch. The token is `<expires>.<signature>`, where
+/// the signature is the hex encoded HMAC-SHA-256 of `<player id>:<expires>`
+/// keyed with the secret issued to the player.
+#[cfg_attr(feature = "bindgen", wasm_bindgen)]
+pub fn player_token(secret: &str, player_id: &str, expires: u64) -> String {
+    let message = format!("{}:{}", player_id, expires);
+    let signature = digest::hmac_sha256(secret.as_bytes(), message.as_bytes());
+    format!("{}.{}", expires, digest::to_hex(&signature))
+}
+
 #[cfg_attr(feature = "bindgen", wasm_bindgen)]
 pub fn rng_test(seed: u64) -> bool {
     let mut rng = Rand64::new(seed as u128);
-    let samples = (0..20)
-        .into_iter()
-        .map(|_| rng.rand_u64())
-        .collect::<Vec<_>>();
+    let samples = (0..20).map(|_| rng.rand_u64()).collect::<Vec<_>>();
 
+    #[cfg(feature = "std")]
     println!("{} {:?}", seed, samples);
     samples
         == [
@@ -187,6 +311,10 @@ impl Cursor {
         }
     }
 
+    fn same_cell(&self, other: &Self) -> bool {
+        self.row == other.row && self.col == other.col
+    }
+
     pub fn prev_along(&self) -> Option<Self> {
         (self.prev_along_f)(self)
     }
@@ -197,37 +325,220 @@ impl Cursor {
 }
 
 #[cfg_attr(feature = "bindgen", wasm_bindgen)]
-impl GameExchange {
+impl Tile {
+    #[cfg_attr(feature = "bindgen", wasm_bindgen(getter))]
+    pub fn value(&self) -> u64 {
+        1 << self.exponent
+    }
+}
 
-    pub fn new(player: String, id: String, score: usize, seed: String, size: usize, moves_str: &str) -> Result<GameExchange, String> {
-        let moves = match serde_json::from_str(moves_str) {
-            Ok(m) => Ok(m),
-            Err(_) => Err("Error parsing moves".to_owned()),
-        }?;
+impl Tile {
+    fn new_blocker(id: usize) -> Self {
+        Tile {
+            id,
+            exponent: 0,
+            merged_with: None,
+            blocker: true,
+        }
+    }
+
+    fn can_merge(&self, other: &Tile) -> bool {
+        !self.blocker && !other.blocker && self.exponent == other.exponent
+    }
+}
+
+impl fmt::Display for Topology {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Topology::Classic => write!(f, "Classic"),
+            Topology::Wraparound => write!(f, "Wraparound"),
+        }
+    }
+}
+
+impl FromStr for Topology {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Classic" => Ok(Topology::Classic),
+            "Wraparound" => Ok(Topology::Wraparound),
+            _ => Err("Invalid topology"),
+        }
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Direction::Right => write!(f, "Right"),
+            Direction::Up => write!(f, "Up"),
+            Direction::Left => write!(f, "Left"),
+            Direction::Down => write!(f, "Down"),
+        }
+    }
+}
+
+impl FromStr for Direction {
+    type Err = &'static str;
 
-        Ok(Self{
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Right" => Ok(Direction::Right),
+            "Up" => Ok(Direction::Up),
+            "Left" => Ok(Direction::Left),
+            "Down" => Ok(Direction::Down),
+            _ => Err("Invalid direction"),
+        }
+    }
+}
+
+impl Direction {
+    /// Parses a direction from its numeric code, as used by the typed-array
+    /// APIs: 0 is Right, 1 is Up, 2 is Left and 3 is Down.
+    pub fn from_code(code: u8) -> Option<Direction> {
+        Direction::ALL.get(code as usize).cloned()
+    }
+
+    pub fn code(&self) -> u8 {
+        self.clone() as u8
+    }
+
+    pub const ALL: [Direction; 4] = [
+        Direction::Right,
+        Direction::Up,
+        Direction::Left,
+        Direction::Down,
+    ];
+}
+
+#[cfg_attr(feature = "bindgen", wasm_bindgen)]
+impl MoveResult {
+    /// True if the move changed the board.
+    pub fn moved(&self) -> bool {
+        self.game.is_some()
+    }
+
+    /// The game after the move, or None if the move was not legal.
+    pub fn game(&self) -> Option<Game> {
+        self.game.clone()
+    }
+
+    pub fn game_over(&self) -> bool {
+        self.game.as_ref().is_some_and(|g| g.game_over)
+    }
+
+    /// The tile spawned after the move, if any.
+    pub fn spawn(&self) -> Option<Spawn> {
+        let index = self.spawned?;
+        let tile = self.game.as_ref()?.tiles[index as usize].as_ref()?;
+        Some(Spawn {
+            index,
+            exponent: tile.exponent,
+        })
+    }
+}
+
+#[cfg_attr(feature = "bindgen", wasm_bindgen)]
+impl Spawn {
+    #[cfg_attr(feature = "bindgen", wasm_bindgen(constructor))]
+    pub fn new(index: u32, exponent: u8) -> Self {
+        Spawn { index, exponent }
+    }
+}
+
+impl Symmetry {
+    pub const ALL: [Symmetry; 8] = [
+        Symmetry::Identity,
+        Symmetry::Rotate90,
+        Symmetry::Rotate180,
+        Symmetry::Rotate270,
+        Symmetry::FlipHorizontal,
+        Symmetry::FlipVertical,
+        Symmetry::Transpose,
+        Symmetry::AntiTranspose,
+    ];
+
+    /// Maps a cell position to its position on the transformed board.
+    /// Rotations are clockwise.
+    fn map(&self, size: usize, row: usize, col: usize) -> (usize, usize) {
+        let last = size - 1;
+        match self {
+            Symmetry::Identity => (row, col),
+            Symmetry::Rotate90 => (col, last - row),
+            Symmetry::Rotate180 => (last - row, last - col),
+            Symmetry::Rotate270 => (last - col, row),
+            Symmetry::FlipHorizontal => (row, last - col),
+            Symmetry::FlipVertical => (last - row, col),
+            Symmetry::Transpose => (col, row),
+            Symmetry::AntiTranspose => (last - col, last - row),
+        }
+    }
+
+    /// Transforms a row-major layout of a board with the given size.
+    fn apply(&self, size: usize, layout: &[u8]) -> Vec<u8> {
+        let mut rv = vec![0; layout.len()];
+        for row in 0..size {
+            for col in 0..size {
+                let (r, c) = self.map(size, row, col);
+                rv[r * size + c] = layout[row * size + col];
+            }
+        }
+        rv
+    }
+}
+
+/// Zobrist key for a tile with the given exponent at the given cell index.
+/// The keys are derived with a splitmix64 finalizer rather than stored in a
+/// table, so that boards of any size can be hashed.
+fn zobrist_key(index: usize, exponent: u8) -> u64 {
+    let mut z = ((index as u64) << 8 | exponent as u64).wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+fn layout_hash(layout: &[u8]) -> u64 {
+    layout
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| **e != 0)
+        .fold(0, |h, (i, e)| h ^ zobrist_key(i, *e))
+}
+
+#[cfg_attr(feature = "bindgen", wasm_bindgen)]
+impl GameExchange {
+    #[cfg(feature = "json")]
+    pub fn new(
+        player: String,
+        id: String,
+        score: u64,
+        seed: String,
+        size: usize,
+        moves_str: &str,
+    ) -> Result<GameExchange, String> {
+        let moves =
+            serde_json::from_str(moves_str).map_err(|_| "Error parsing moves".to_owned())?;
+
+        Ok(Self {
             player,
             id,
             score,
             seed,
             size,
-            moves
+            topology: Topology::Classic,
+            blockers: vec![],
+            moves,
         })
     }
-        
+
+    #[cfg(feature = "json")]
     pub fn from_json(json: String) -> Option<GameExchange> {
-        let parsed: serde_json::Result<GameExchange> = serde_json::from_str(&json);
-        match parsed {
-            Ok(game) => Some(game),
-            _ => None,
-        }
+        serde_json::from_str(&json).ok()
     }
 
     pub fn to_game(&self) -> Option<Game> {
-        match Game::try_from(self) {
-            Ok(game) => Some(game),
-            _ => None,
-        }
+        Game::try_from(self).ok()
     }
 
     pub fn from_game(g: &Game) -> GameExchange {
@@ -242,19 +553,75 @@ impl GameExchange {
         self.player = name;
     }
 
+    pub fn get_id(&self) -> String {
+        self.id.clone()
+    }
+
     pub fn get_size(&self) -> usize {
         self.size
     }
 
+    pub fn get_topology(&self) -> Topology {
+        self.topology
+    }
+
+    pub fn set_topology(&mut self, topology: Topology) {
+        self.topology = topology;
+    }
+
+    pub fn get_blockers(&self) -> Vec<usize> {
+        self.blockers.clone()
+    }
+
+    pub fn set_blockers(&mut self, blockers: Vec<usize>) {
+        self.blockers = blockers;
+    }
+
+    #[cfg(feature = "json")]
+    pub fn get_blockers_str(&self) -> String {
+        serde_json::to_string(&self.blockers).unwrap()
+    }
+
+    /// Creates an exchange with moves given as direction codes, see
+    /// `Direction::from_code`.
+    pub fn new_from_codes(
+        player: String,
+        id: String,
+        score: u64,
+        seed: String,
+        size: usize,
+        moves: &[u8],
+    ) -> Result<GameExchange, String> {
+        let moves = moves
+            .iter()
+            .map(|c| Direction::from_code(*c))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| "Error parsing moves".to_owned())?;
+
+        Ok(Self {
+            player,
+            id,
+            score,
+            seed,
+            size,
+            topology: Topology::Classic,
+            blockers: vec![],
+            moves,
+        })
+    }
+
+    pub fn get_moves(&self) -> Vec<u8> {
+        self.moves.iter().map(Direction::code).collect()
+    }
+
+    #[cfg(feature = "json")]
     pub fn get_moves_str(&self) -> String {
         serde_json::to_string(&self.moves).unwrap()
     }
 
+    #[cfg(feature = "json")]
     pub fn to_json(&self) -> Option<String> {
-        match serde_json::to_string(self) {
-            Ok(json) => Some(json),
-            _ => None,
-        }
+        serde_json::to_string(self).ok()
     }
 }
 
@@ -262,39 +629,140 @@ impl GameExchange {
 impl Game {
     #[cfg(feature = "random")]
     pub fn new(size: usize) -> Self {
-        Self::new_from_seed(size, WyRand::new().generate(), &Uuid::new_v4().to_hyphenated().to_string())
+        Self::new_from_seed(
+            size,
+            WyRand::new().generate(),
+            &Uuid::new_v4().to_hyphenated().to_string(),
+        )
     }
 
     pub fn new_from_seed(size: usize, seed: u64, id: &str) -> Self {
+        Self::new_from_seed_with_topology(size, seed, id, Topology::Classic)
+    }
+
+    pub fn new_from_seed_with_topology(
+        size: usize,
+        seed: u64,
+        id: &str,
+        topology: Topology,
+    ) -> Self {
+        Self::new_from_definition(size, seed, id, topology, vec![]).unwrap()
+    }
+
+    /// Creates a game with blockers at the given cell indices. Returns None
+    /// if the definition is invalid: a blocker is outside the board, a cell
+    /// is given twice, or there is no room left for the two starting tiles,
+    /// which includes boards smaller than 2x2.
+    pub fn new_from_definition(
+        size: usize,
+        seed: u64,
+        id: &str,
+        topology: Topology,
+        blockers: Vec<usize>,
+    ) -> Option<Game> {
+        Self::new_without_tiles(size, seed, id, topology, blockers)?
+            .add_tile()?
+            .add_tile()
+    }
+
+    /// Creates a game with only the blockers on the board, for games whose
+    /// tiles are spawned by a server. The starting tiles are placed with
+    /// `place_spawn`. Returns None if the blockers are invalid.
+    pub fn new_unseeded(
+        size: usize,
+        id: &str,
+        topology: Topology,
+        blockers: Vec<usize>,
+    ) -> Option<Game> {
+        Self::new_without_tiles(size, 0, id, topology, blockers)
+    }
+
+    fn new_without_tiles(
+        size: usize,
+        seed: u64,
+        id: &str,
+        topology: Topology,
+        blockers: Vec<usize>,
+    ) -> Option<Game> {
+        let count = blockers.len();
+
+        let mut blockers = blockers;
+        blockers.sort_unstable();
+        blockers.dedup();
+        if blockers.len() != count
+            || blockers.iter().any(|i| *i >= size * size)
+            || count + 2 > size * size
+        {
+            return None;
+        }
+
+        let mut tiles = vec![None; size * size];
+        for (tile_id, index) in blockers.iter().enumerate() {
+            tiles[*index] = Some(Tile::new_blocker(tile_id));
+        }
         let rng = Rand64::new(seed as u128);
-        Game {
+        Some(Game {
             id: id.to_owned(),
             score: 0,
             game_over: false,
             seed,
             rng,
             size,
-            next_tile_id: 0,
-            tiles: vec![None; size * size],
+            topology,
+            next_tile_id: count,
+            tiles,
             moves: vec![],
+        })
+    }
+
+    /// Picks blocker cells for a game from its seed. The blockers are drawn
+    /// from a separate random stream, so the tiles spawned during the game
+    /// are the same as for the same seed without blockers. The count is
+    /// limited to leave room for the two starting tiles.
+    pub fn blockers_from_seed(size: usize, seed: u64, count: usize) -> Vec<usize> {
+        let mut rng = Rand64::new(1 << 64 | seed as u128);
+        let mut cells = (0..size * size).collect::<Vec<_>>();
+        let count = count.min((size * size).saturating_sub(2));
+        for i in 0..count {
+            let j = i + rng.rand_range(0..(cells.len() - i) as u64) as usize;
+            cells.swap(i, j);
         }
-        .add_tile()
-        .unwrap()
-        .add_tile()
-        .unwrap()
+        let mut blockers = cells[..count].to_vec();
+        blockers.sort_unstable();
+        blockers
+    }
+
+    /// Creates a game and plays the moves, given as direction codes. Returns
+    /// None if the definition is invalid or a move is not legal.
+    pub fn from_moves(
+        size: usize,
+        seed: u64,
+        id: &str,
+        topology: Topology,
+        blockers: Vec<usize>,
+        moves: &[u8],
+    ) -> Option<Game> {
+        let game = Game::new_from_definition(size, seed, id, topology, blockers)?;
+        game.apply_moves(moves)
     }
 
     pub fn from_exchange(gx: &GameExchange) -> Option<Game> {
-        match Game::try_from(gx) {
-            Ok(game) => Some(game),
-            _ => None,
-        }
+        Game::try_from(gx).ok()
     }
 
     pub fn to_exchange(&self) -> GameExchange {
         GameExchange::from(self)
     }
 
+    /// Parses a game from the binary replay format, ignoring any timestamps.
+    pub fn from_replay_bytes(bytes: &[u8]) -> Option<Game> {
+        Replay::from_bytes(bytes).ok().map(Replay::into_game)
+    }
+
+    pub fn to_replay_bytes(&self) -> Option<Vec<u8>> {
+        Replay::new(self.clone()).to_bytes().ok()
+    }
+
     pub fn get_id(&self) -> String {
         self.id.clone()
     }
@@ -307,7 +775,23 @@ impl Game {
         self.size
     }
 
-    pub fn get_score(&self) -> usize {
+    pub fn get_topology(&self) -> Topology {
+        self.topology
+    }
+
+    /// Returns the row-major cell indices of the blockers on the board.
+    pub fn get_blockers(&self) -> Vec<usize> {
+        self.tiles
+            .iter()
+            .enumerate()
+            .filter_map(|(i, t)| match t {
+                Some(tile) if tile.blocker => Some(i),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn get_score(&self) -> u64 {
         self.score
     }
 
@@ -315,10 +799,106 @@ impl Game {
         self.game_over
     }
 
+    /// Value of the highest tile on the board, 0 if there are no tiles.
+    /// Tiles only grow, so this is the highest tile reached in the game.
+    pub fn max_tile(&self) -> u64 {
+        self.tiles
+            .iter()
+            .flatten()
+            .filter(|t| !t.blocker)
+            .map(|t| 1 << t.exponent)
+            .max()
+            .unwrap_or(0)
+    }
+
     pub fn debug(&self) -> String {
         format!("{:?}", self)
     }
 
+    /// Returns the board as row-major tile exponents, with 0 for empty
+    /// cells and 255 for blockers. Tile ids are not included, so two boards
+    /// with the same tiles in the same places have equal layouts.
+    pub fn layout(&self) -> Vec<u8> {
+        self.tiles
+            .iter()
+            .map(|t| match t {
+                Some(tile) if tile.blocker => u8::MAX,
+                Some(tile) => tile.exponent,
+                None => 0,
+            })
+            .collect()
+    }
+
+    pub fn symmetric_layout(&self, symmetry: Symmetry) -> Vec<u8> {
+        symmetry.apply(self.size, &self.layout())
+    }
+
+    /// Returns the lexicographically smallest layout among the eight
+    /// rotations and reflections of the board.
+    pub fn canonical_layout(&self) -> Vec<u8> {
+        self.canonical_symmetry_and_layout().1
+    }
+
+    /// Returns the symmetry which transforms the board into its canonical
+    /// layout.
+    pub fn canonical_symmetry(&self) -> Symmetry {
+        self.canonical_symmetry_and_layout().0
+    }
+
+    pub fn same_layout(&self, other: &Game) -> bool {
+        self.size == other.size && self.layout() == other.layout()
+    }
+
+    pub fn same_canonical_layout(&self, other: &Game) -> bool {
+        self.size == other.size && self.canonical_layout() == other.canonical_layout()
+    }
+
+    /// Returns the row-major tile ids, with `NO_TILE` for empty cells.
+    pub fn tile_ids(&self) -> Vec<u32> {
+        self.tiles
+            .iter()
+            .map(|t| t.as_ref().map_or(NO_TILE, |tile| tile.id as u32))
+            .collect()
+    }
+
+    /// Returns, for each cell, the id of the tile which merged into the tile
+    /// there during the last move, or `NO_TILE`.
+    pub fn merged_ids(&self) -> Vec<u32> {
+        self.tiles
+            .iter()
+            .map(|t| {
+                t.as_ref()
+                    .and_then(|tile| tile.merged_with)
+                    .map_or(NO_TILE, |id| id as u32)
+            })
+            .collect()
+    }
+
+    /// Returns the moves as direction codes, see `Direction::from_code`.
+    pub fn get_moves(&self) -> Vec<u8> {
+        self.moves.iter().map(Direction::code).collect()
+    }
+
+    /// Zobrist hash of the board layout, ignoring tile ids.
+    pub fn layout_hash(&self) -> u64 {
+        layout_hash(&self.layout())
+    }
+
+    /// Zobrist hash of the canonical layout, equal for all boards which are
+    /// rotations or reflections of each other.
+    pub fn canonical_hash(&self) -> u64 {
+        layout_hash(&self.canonical_layout())
+    }
+
+    fn canonical_symmetry_and_layout(&self) -> (Symmetry, Vec<u8>) {
+        let layout = self.layout();
+        Symmetry::ALL
+            .iter()
+            .map(|s| (*s, s.apply(self.size, &layout)))
+            .min_by(|(_, a), (_, b)| a.cmp(b))
+            .unwrap()
+    }
+
     fn can_move(&self, d: Direction) -> bool {
         let mut across_cursor_option = Some(Cursor::new(self.size, d));
 
@@ -335,7 +915,7 @@ impl Game {
             while let Some(prev_cursor) = prev_cursor_option {
                 let prev_tile = self.get_tile(prev_cursor.row(), prev_cursor.col()).unwrap();
 
-                if along_tile.value == prev_tile.value {
+                if along_tile.can_merge(&prev_tile) {
                     return true;
                 }
 
@@ -343,6 +923,10 @@ impl Game {
                 prev_cursor_option = prev_cursor.prev_along();
             }
 
+            if self.seam_pair(&across_cursor).is_some() {
+                return true;
+            }
+
             across_cursor_option = across_cursor.next_across();
         }
         false
@@ -361,27 +945,46 @@ impl Game {
         if self.game_over {
             return None;
         }
-        let mut rng = self.rng.clone();
+        let mut rng = self.rng;
         let empty_indices = self
             .tiles
             .iter()
             .enumerate()
             .filter_map(|(i, tile)| if tile.is_none() { Some(i) } else { None })
             .collect::<Vec<_>>();
-        if empty_indices.len() == 0 {
+        if empty_indices.is_empty() {
             return None;
         }
         let index = empty_indices[rng.rand_range(0..empty_indices.len() as u64) as usize];
-        let value = if rng.rand_range(0..9) == 0 { 4 } else { 2 };
+        let exponent = if rng.rand_range(0..9) == 0 { 2 } else { 1 };
+        let mut rv = self.place_spawn(Spawn {
+            index: index as u32,
+            exponent,
+        })?;
+        rv.rng = rng;
+        Some(rv)
+    }
+
+    /// Places a tile without using the random number generator, for games
+    /// whose tiles are spawned by a server. Returns None if the game is over,
+    /// the cell is not empty or the exponent is not a tile exponent.
+    pub fn place_spawn(&self, spawn: Spawn) -> Option<Game> {
+        if self.game_over || spawn.exponent == 0 || spawn.exponent == u8::MAX {
+            return None;
+        }
+        let index = spawn.index as usize;
+        if self.tiles.get(index)?.is_some() {
+            return None;
+        }
         let mut tiles = self.tiles.clone();
         tiles[index] = Some(Tile {
             id: self.next_tile_id,
-            value,
+            exponent: spawn.exponent,
             merged_with: None,
+            blocker: false,
         });
         let mut rv = Game {
             id: self.id.clone(),
-            rng,
             next_tile_id: self.next_tile_id + 1,
             tiles,
             moves: self.moves.clone(),
@@ -403,15 +1006,28 @@ impl Game {
         if self.game_over {
             return None;
         }
-        let mut changed = false;
         let mut rv = self.clone();
-        let mut across_cursor_option = Some(Cursor::new(self.size, d.clone()));
 
-        for t in &mut rv.tiles {
-            if let Some(ref mut tile) = t {
-                tile.merged_with = None;
-            }
+        for tile in rv.tiles.iter_mut().flatten() {
+            tile.merged_with = None;
         }
+        let changed = match self.topology {
+            Topology::Classic => rv.slide_classic(d.clone()),
+            Topology::Wraparound => rv.slide_wraparound(d.clone()),
+        };
+        if changed {
+            rv.moves.push(d);
+            rv.update_game_over();
+            Some(rv)
+        } else {
+            None
+        }
+    }
+
+    fn slide_classic(&mut self, d: Direction) -> bool {
+        let mut changed = false;
+        let mut across_cursor_option = Some(Cursor::new(self.size, d));
+
         // Outer loop over the across direction
         while let Some(across_cursor) = across_cursor_option {
             let mut dst_cursor = across_cursor.clone();
@@ -421,7 +1037,12 @@ impl Game {
             while let Some(ref src_cursor) = src_cursor_option {
                 let src_row = src_cursor.row();
                 let src_col = src_cursor.col();
-                match rv.get_tile(src_row, src_col) {
+                match self.get_tile(src_row, src_col) {
+                    Some(src_tile) if src_tile.blocker => {
+                        // Tiles behind a blocker slide up against it
+                        dst_cursor = src_cursor.clone();
+                        src_cursor_option = src_cursor.prev_along();
+                    }
                     Some(src_tile) => {
                         // The source tile contains a tile
                         let dst_row = dst_cursor.row();
@@ -431,23 +1052,20 @@ impl Game {
                             // Source and destination are the same - step the source
                             src_cursor_option = src_cursor.prev_along();
                         } else {
-                            match rv.get_tile(dst_row, dst_col) {
+                            match self.get_tile(dst_row, dst_col) {
                                 Some(dst_tile) => {
                                     // The destination contains a tile
-                                    if src_tile.value == dst_tile.value {
+                                    if src_tile.can_merge(&dst_tile) {
                                         // Merge tiles of equal value
-                                        let new_value = src_tile.value + dst_tile.value;
-                                        rv.set_tile(
-                                            dst_row,
-                                            dst_col,
-                                            Some(Tile {
-                                                id: src_tile.id,
-                                                value: new_value,
-                                                merged_with: Some(dst_tile.id),
-                                            }),
-                                        );
-                                        rv.set_tile(src_row, src_col, None);
-                                        rv.score += new_value;
+                                        let new_tile = Tile {
+                                            id: src_tile.id,
+                                            exponent: src_tile.exponent + 1,
+                                            merged_with: Some(dst_tile.id),
+                                            blocker: false,
+                                        };
+                                        self.score += new_tile.value();
+                                        self.set_tile(dst_row, dst_col, Some(new_tile));
+                                        self.set_tile(src_row, src_col, None);
                                         // Step the source
                                         src_cursor_option = src_cursor.prev_along();
                                         changed = true;
@@ -457,8 +1075,8 @@ impl Game {
                                 }
                                 None => {
                                     // No tile in destination - move the source tile
-                                    rv.set_tile(dst_row, dst_col, Some(src_tile));
-                                    rv.set_tile(src_row, src_col, None);
+                                    self.set_tile(dst_row, dst_col, Some(src_tile));
+                                    self.set_tile(src_row, src_col, None);
                                     // Step the source
                                     src_cursor_option = src_cursor.prev_along();
                                     changed = true;
@@ -475,21 +1093,149 @@ impl Game {
 
             across_cursor_option = across_cursor.next_across();
         }
-        if changed {
-            rv.moves.push(d);
-            rv.update_game_over();
-            Some(rv)
+        changed
+    }
+
+    /// Returns the cells of the leading and the trailing tile of the line
+    /// which starts at the cursor, if the board is a wraparound board and the
+    /// two tiles can merge across the edge. Blockers break the ring, so the
+    /// outermost occupied cells must both hold tiles.
+    fn seam_pair(&self, across_cursor: &Cursor) -> Option<(Cursor, Cursor)> {
+        if self.topology != Topology::Wraparound {
+            return None;
+        }
+        let mut occupied = Vec::new();
+        let mut cursor_option = Some(across_cursor.clone());
+        while let Some(cursor) = cursor_option {
+            if self.get_tile(cursor.row(), cursor.col()).is_some() {
+                occupied.push(cursor.clone());
+            }
+            cursor_option = cursor.prev_along();
+        }
+        let leading = occupied.first()?;
+        let trailing = occupied.last()?;
+        if leading.same_cell(trailing) {
+            return None;
+        }
+        let leading_tile = self.get_tile(leading.row(), leading.col())?;
+        let trailing_tile = self.get_tile(trailing.row(), trailing.col())?;
+        if leading_tile.can_merge(&trailing_tile) {
+            Some((leading.clone(), trailing.clone()))
         } else {
             None
         }
     }
 
+    /// Slides the tiles on a wraparound board. Each line is first slid as on
+    /// the classic board. The leading and trailing tiles of the line are then
+    /// neighbours across the edge: if neither has merged during the move and
+    /// they are equal, the trailing tile crosses the edge and merges into the
+    /// leading tile. The trailing tile is the farthest from where its part
+    /// of the line compacts to, so no gap is left behind.
+    fn slide_wraparound(&mut self, d: Direction) -> bool {
+        let mut changed = self.slide_classic(d.clone());
+        let mut across_cursor_option = Some(Cursor::new(self.size, d));
+
+        // Outer loop over the across direction
+        while let Some(across_cursor) = across_cursor_option {
+            if let Some((leading, trailing)) = self.seam_pair(&across_cursor) {
+                let leading_tile = self.get_tile(leading.row(), leading.col()).unwrap();
+                let trailing_tile = self.get_tile(trailing.row(), trailing.col()).unwrap();
+                // A tile merges at most once per move
+                if leading_tile.merged_with.is_none() && trailing_tile.merged_with.is_none() {
+                    // Merge tiles of equal value
+                    let new_tile = Tile {
+                        id: trailing_tile.id,
+                        exponent: trailing_tile.exponent + 1,
+                        merged_with: Some(leading_tile.id),
+                        blocker: false,
+                    };
+                    self.score += new_tile.value();
+                    self.set_tile(leading.row(), leading.col(), Some(new_tile));
+                    self.set_tile(trailing.row(), trailing.col(), None);
+                    changed = true;
+                }
+            }
+
+            across_cursor_option = across_cursor.next_across();
+        }
+        changed
+    }
+
+    /// Returns true if sliding in the given direction changes the board.
+    pub fn is_legal_move(&self, d: Direction) -> bool {
+        self.slide(d).is_some()
+    }
+
+    /// Returns the board after sliding in the given direction, before a new
+    /// tile is spawned. The random number generator is not advanced, so the
+    /// preview can be shown without affecting the game. Returns None if the
+    /// move is not legal.
+    pub fn preview(&self, d: Direction) -> Option<Game> {
+        self.slide(d)
+    }
+
+    /// Returns the codes of the directions which change the board, so a
+    /// client can disable the others.
+    pub fn legal_moves_codes(&self) -> Vec<u8> {
+        self.legal_moves().iter().map(Direction::code).collect()
+    }
+
+    /// Returns the layout of `preview`, or None if the move is not legal.
+    pub fn preview_layout(&self, d: Direction) -> Option<Vec<u8>> {
+        self.preview(d).map(|g| g.layout())
+    }
+
     pub fn make_move(&self, d: Direction) -> Option<Game> {
         let game = self.slide(d)?;
         let game = game.add_tile()?;
         Some(game)
     }
 
+    /// Makes a move with a tile spawned by a server instead of the random
+    /// number generator. Returns None if the move is not legal or the spawn
+    /// cannot be placed.
+    pub fn make_move_with_spawn(&self, d: Direction, spawn: Spawn) -> Option<Game> {
+        self.slide(d)?.place_spawn(spawn)
+    }
+
+    /// Makes a move and reports what changed. The result holds no game if
+    /// the move is not legal.
+    pub fn play(&self, d: Direction) -> MoveResult {
+        let slid = self.slide(d);
+        let game = slid.as_ref().and_then(Game::add_tile);
+        let (score_gained, merges, spawned) = match (&slid, &game) {
+            (Some(slid), Some(game)) => (
+                game.score - self.score,
+                slid.tiles
+                    .iter()
+                    .flatten()
+                    .filter(|t| t.merged_with.is_some())
+                    .count() as u32,
+                slid.tiles
+                    .iter()
+                    .zip(game.tiles.iter())
+                    .position(|(a, b)| a.is_none() && b.is_some())
+                    .map(|i| i as u32),
+            ),
+            _ => (0, 0, None),
+        };
+        MoveResult {
+            game,
+            score_gained,
+            merges,
+            spawned,
+        }
+    }
+
+    /// Plays the moves, given as direction codes. Returns None if a code is
+    /// invalid or a move is not legal.
+    pub fn apply_moves(&self, moves: &[u8]) -> Option<Game> {
+        moves
+            .iter()
+            .try_fold(self.clone(), |g, c| g.make_move(Direction::from_code(*c)?))
+    }
+
     pub fn is_ancestor(&self, other: &Game) -> bool {
         if self.id != other.id {
             return false;
@@ -500,6 +1246,9 @@ impl Game {
         if self.size != other.size {
             return false;
         }
+        if self.topology != other.topology {
+            return false;
+        }
         if self.moves.len() > other.moves.len() {
             return false;
         }
@@ -524,6 +1273,116 @@ impl Game {
     }
 }
 
+impl Game {
+    /// Returns the directions which change the board, in the order of
+    /// `Direction::ALL`.
+    pub fn legal_moves(&self) -> Vec<Direction> {
+        Direction::ALL
+            .iter()
+            .filter(|d| self.is_legal_move((*d).clone()))
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the tiles spawned during the game, starting with the two
+    /// starting tiles, so that the game can be rebuilt with `new_unseeded`,
+    /// `place_spawn` and `make_move_with_spawn` without knowing the seed.
+    pub fn spawns(&self) -> Vec<Spawn> {
+        let mut game = match Game::new_from_definition(
+            self.size,
+            self.seed,
+            &self.id,
+            self.topology,
+            self.get_blockers(),
+        ) {
+            Some(game) => game,
+            None => return vec![],
+        };
+        let mut starting = game
+            .tiles
+            .iter()
+            .enumerate()
+            .filter_map(|(index, tile)| match tile {
+                Some(tile) if !tile.blocker => Some((tile.id, index)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        starting.sort_unstable();
+        let mut spawns = starting
+            .into_iter()
+            .map(|(_, index)| {
+                Spawn::new(index as u32, game.tiles[index].as_ref().unwrap().exponent)
+            })
+            .collect::<Vec<_>>();
+        for d in self.moves.iter() {
+            let result = game.play(d.clone());
+            match result.game() {
+                Some(g) => {
+                    spawns.extend(result.spawn());
+                    game = g;
+                }
+                None => break,
+            }
+        }
+        spawns
+    }
+
+    /// Heuristic value of the board for move selection, higher is better.
+    /// Rewards empty cells and a large tile in a corner, and penalizes lines
+    /// which are not monotonic and neighbouring tiles of different values.
+    /// Blockers are ignored.
+    pub fn evaluate(&self) -> i64 {
+        let layout = self
+            .layout()
+            .iter()
+            .map(|e| if *e == u8::MAX { 0 } else { *e as i64 })
+            .collect::<Vec<_>>();
+        let size = self.size;
+        let at = |row: usize, col: usize| layout[row * size + col];
+
+        let empty = self.tiles.iter().filter(|t| t.is_none()).count() as i64;
+        let max = layout.iter().copied().max().unwrap_or(0);
+        let last = size.saturating_sub(1);
+        let corner = [(0, 0), (0, last), (last, 0), (last, last)]
+            .iter()
+            .any(|(r, c)| at(*r, *c) == max);
+
+        let mut roughness = 0;
+        let mut unordered = 0;
+        for i in 0..size {
+            let (mut row_up, mut row_down, mut col_up, mut col_down) = (0, 0, 0, 0);
+            for j in 1..size {
+                let (a, b) = (at(i, j - 1), at(i, j));
+                if a != 0 && b != 0 {
+                    roughness += (a - b).abs();
+                }
+                row_up += (a - b).max(0);
+                row_down += (b - a).max(0);
+
+                let (a, b) = (at(j - 1, i), at(j, i));
+                if a != 0 && b != 0 {
+                    roughness += (a - b).abs();
+                }
+                col_up += (a - b).max(0);
+                col_down += (b - a).max(0);
+            }
+            unordered += row_up.min(row_down) + col_up.min(col_down);
+        }
+
+        empty * 256 + if corner { max * 64 } else { 0 } - unordered * 48 - roughness * 16
+    }
+
+    /// Returns the legal move whose slide gives the best `evaluate` value,
+    /// or None if the game is over. Spawned tiles are not considered.
+    pub fn best_move(&self) -> Option<Direction> {
+        Direction::ALL
+            .iter()
+            .filter_map(|d| Some((d.clone(), self.preview(d.clone())?)))
+            .max_by_key(|(_, g)| (g.score - self.score) as i64 + g.evaluate())
+            .map(|(d, _)| d)
+    }
+}
+
 impl From<&Game> for GameExchange {
     fn from(g: &Game) -> Self {
         GameExchange {
@@ -532,6 +1391,8 @@ impl From<&Game> for GameExchange {
             score: g.score,
             seed: g.seed.to_string(),
             size: g.size,
+            topology: g.topology,
+            blockers: g.get_blockers(),
             moves: g.moves.clone(),
         }
     }
@@ -545,7 +1406,9 @@ impl TryFrom<&GameExchange> for Game {
             Ok(s) => Ok(s),
             Err(_) => Err("Invalid seed"),
         }?;
-        let mut g = Game::new_from_seed(gx.size, seed, &gx.id);
+        let mut g =
+            Game::new_from_definition(gx.size, seed, &gx.id, gx.topology, gx.blockers.clone())
+                .ok_or("Invalid game definition")?;
         for d in &gx.moves {
             match g.make_move(d.clone()) {
                 Some(new_g) => g = new_g,
@@ -573,6 +1436,7 @@ mod tests {
             seed: 0,
             rng: Rand64::new(0),
             size: 4,
+            topology: Topology::Classic,
             next_tile_id: 0,
             moves: vec![],
             tiles: [
@@ -591,14 +1455,16 @@ mod tests {
                 None,
                 Some(Tile {
                     id: 0,
-                    value: 2,
+                    exponent: 1,
                     merged_with: None,
+                    blocker: false,
                 }),
                 None,
                 Some(Tile {
                     id: 1,
-                    value: 2,
+                    exponent: 1,
                     merged_with: None,
+                    blocker: false,
                 }),
             ]
             .to_vec(),
@@ -621,8 +1487,9 @@ mod tests {
                     None,
                     Some(Tile {
                         id: 1,
-                        value: 4,
-                        merged_with: Some(0)
+                        exponent: 2,
+                        merged_with: Some(0),
+                        blocker: false,
                     }),
                     None,
                     None,
@@ -631,6 +1498,381 @@ mod tests {
         );
     }
 
+    fn row_game(topology: Topology, row: [u8; 4]) -> Game {
+        let mut game = Game::new_from_seed_with_topology(4, 0, "", topology);
+        game.tiles = vec![None; 16];
+        for (col, exponent) in row.iter().enumerate() {
+            if *exponent != 0 {
+                game.set_tile(
+                    0,
+                    col,
+                    Some(Tile {
+                        id: col,
+                        exponent: *exponent,
+                        merged_with: None,
+                        blocker: false,
+                    }),
+                );
+            }
+        }
+        game
+    }
+
+    fn row_layout(game: &Game) -> Vec<u8> {
+        game.layout()[..4].to_vec()
+    }
+
+    fn board_game(topology: Topology, layout: [u8; 16]) -> Game {
+        let mut game = Game::new_from_seed_with_topology(4, 0, "", topology);
+        game.tiles = layout
+            .iter()
+            .enumerate()
+            .map(|(id, exponent)| {
+                if *exponent == 0 {
+                    None
+                } else {
+                    Some(Tile {
+                        id,
+                        exponent: *exponent,
+                        merged_with: None,
+                        blocker: false,
+                    })
+                }
+            })
+            .collect();
+        game
+    }
+
+    #[test]
+    fn wraparound_slide_test() {
+        // Tiles compact as on the classic board, and the two ends of a line
+        // merge across the edge when neither merged with its other neighbour
+        let cases = [
+            ([1, 0, 0, 2], Direction::Right, [0, 0, 1, 2], 0),
+            ([0, 1, 0, 1], Direction::Right, [0, 0, 0, 2], 4),
+            ([0, 0, 0, 1], Direction::Right, [0, 0, 0, 1], 0),
+            ([0, 2, 0, 0], Direction::Right, [0, 0, 0, 2], 0),
+            ([1, 0, 1, 0], Direction::Left, [2, 0, 0, 0], 4),
+            ([1, 2, 3, 4], Direction::Right, [1, 2, 3, 4], 0),
+            ([1, 2, 3, 1], Direction::Right, [0, 2, 3, 2], 4),
+            ([1, 2, 3, 1], Direction::Left, [2, 2, 3, 0], 4),
+            ([2, 1, 0, 2], Direction::Right, [0, 0, 1, 3], 8),
+            ([1, 1, 2, 3], Direction::Right, [0, 2, 2, 3], 4),
+            ([1, 1, 2, 1], Direction::Right, [0, 2, 2, 1], 4),
+            ([1, 3, 1, 1], Direction::Right, [0, 1, 3, 2], 4),
+        ];
+        for (row, d, expected, score) in cases.iter() {
+            let game = row_game(Topology::Wraparound, *row);
+            let slid = game.slide(d.clone()).unwrap_or_else(|| game.clone());
+            assert_eq!(row_layout(&slid), expected.to_vec(), "{:?} {:?}", row, d);
+            assert_eq!(slid.get_score(), *score, "{:?} {:?}", row, d);
+        }
+
+        let classic = row_game(Topology::Classic, [1, 2, 3, 1]);
+        assert!(classic.slide(Direction::Right).is_none());
+
+        // Columns are rings too
+        let mut layout = [0; 16];
+        layout[0] = 1;
+        layout[4] = 2;
+        layout[12] = 1;
+        let game = board_game(Topology::Wraparound, layout);
+        let slid = game.slide(Direction::Up).unwrap();
+        assert_eq!(slid.layout(), {
+            let mut expected = [0; 16];
+            expected[0] = 2;
+            expected[4] = 2;
+            expected.to_vec()
+        });
+        assert_eq!(slid.get_tile(0, 0).unwrap().merged_with, Some(0));
+        assert_eq!(slid.get_tile(0, 0).unwrap().id, 12);
+    }
+
+    #[test]
+    fn wraparound_game_over_test() {
+        // No neighbours are equal on the classic board
+        let mut layout = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16];
+
+        // The ends of the first row are neighbours across the edge
+        layout[3] = 1;
+        let mut game = board_game(Topology::Wraparound, layout);
+        game.update_game_over();
+        assert!(!game.get_game_over());
+        assert!(game.is_legal_move(Direction::Right));
+        assert!(game.is_legal_move(Direction::Left));
+        assert!(!game.is_legal_move(Direction::Up));
+        let mut classic = board_game(Topology::Classic, layout);
+        classic.update_game_over();
+        assert!(classic.get_game_over());
+
+        // The ends of the second column are neighbours across the edge
+        layout[3] = 4;
+        layout[13] = 2;
+        let mut game = board_game(Topology::Wraparound, layout);
+        game.update_game_over();
+        assert!(!game.get_game_over());
+        assert_eq!(game.legal_moves(), vec![Direction::Up, Direction::Down]);
+
+        layout[13] = 14;
+        let mut game = board_game(Topology::Wraparound, layout);
+        game.update_game_over();
+        assert!(game.get_game_over());
+    }
+
+    #[test]
+    fn wraparound_exchange_test() {
+        let mut game = Game::new_from_seed_with_topology(4, 0, "", Topology::Wraparound);
+        for d in Direction::ALL.iter().cycle().take(20) {
+            if let Some(g) = game.make_move(d.clone()) {
+                game = g;
+            }
+        }
+        let gx = GameExchange::from_json(game.to_exchange().to_json().unwrap()).unwrap();
+        assert_eq!(gx.get_topology(), Topology::Wraparound);
+        assert!(gx.to_game().unwrap() == game);
+
+        let mut classic_gx = gx.clone();
+        classic_gx.set_topology(Topology::Classic);
+        assert!(classic_gx.to_game() != Some(game));
+    }
+
+    #[test]
+    fn blocker_slide_test() {
+        for topology in [Topology::Classic, Topology::Wraparound].iter() {
+            let mut game = row_game(*topology, [1, 0, 2, 0]);
+            game.set_tile(0, 1, Some(Tile::new_blocker(10)));
+            let slid = game.slide(Direction::Right).unwrap();
+            assert_eq!(row_layout(&slid), vec![1, u8::MAX, 0, 2]);
+            assert_eq!(
+                row_layout(&slid.slide(Direction::Left).unwrap()),
+                vec![1, u8::MAX, 2, 0]
+            );
+        }
+
+        // A blocker does not separate the ends of a line across the edge
+        let mut game = row_game(Topology::Wraparound, [1, 0, 0, 1]);
+        game.set_tile(0, 1, Some(Tile::new_blocker(10)));
+        let slid = game.slide(Direction::Right).unwrap();
+        assert_eq!(row_layout(&slid), vec![0, u8::MAX, 0, 2]);
+        // unless it is at one of the ends
+        let mut game = row_game(Topology::Wraparound, [0, 1, 2, 1]);
+        game.set_tile(0, 0, Some(Tile::new_blocker(10)));
+        assert!(game.slide(Direction::Left).is_none());
+
+        let mut game = row_game(Topology::Classic, [1, 1, 0, 0]);
+        game.set_tile(0, 2, Some(Tile::new_blocker(10)));
+        game.set_tile(0, 3, Some(Tile::new_blocker(11)));
+        let slid = game.slide(Direction::Right).unwrap();
+        assert_eq!(row_layout(&slid), vec![0, 2, u8::MAX, u8::MAX]);
+        assert!(slid.slide(Direction::Right).is_none());
+    }
+
+    #[test]
+    fn blocker_exchange_test() {
+        let blockers = Game::blockers_from_seed(4, 7, 3);
+        assert_eq!(blockers, Game::blockers_from_seed(4, 7, 3));
+        assert_eq!(blockers.len(), 3);
+
+        let mut game =
+            Game::new_from_definition(4, 7, "", Topology::Classic, blockers.clone()).unwrap();
+        assert_eq!(game.get_blockers(), blockers);
+        assert_eq!(game.tiles.iter().flatten().count(), 5);
+        for d in Direction::ALL.iter().cycle().take(20) {
+            if let Some(g) = game.make_move(d.clone()) {
+                game = g;
+            }
+        }
+        assert_eq!(game.get_blockers(), blockers);
+
+        let gx = GameExchange::from_json(game.to_exchange().to_json().unwrap()).unwrap();
+        assert_eq!(gx.get_blockers(), blockers);
+        assert!(gx.to_game().unwrap() == game);
+
+        let mut gx = gx;
+        gx.set_blockers(vec![0, 0]);
+        assert!(gx.to_game().is_none());
+        assert!(Game::new_from_definition(2, 7, "", Topology::Classic, vec![0, 1, 2]).is_none());
+        assert!(Game::new_from_definition(4, 7, "", Topology::Classic, vec![16]).is_none());
+
+        let json = Game::new_from_seed(4, 0, "")
+            .to_exchange()
+            .to_json()
+            .unwrap();
+        assert!(!json.contains("blockers"));
+    }
+
+    #[test]
+    fn legal_moves_test() {
+        let mut game = Game::new_from_seed(4, 0, "");
+        game.tiles = vec![None; 16];
+        game.set_tile(
+            0,
+            0,
+            Some(Tile {
+                id: 0,
+                exponent: 1,
+                merged_with: None,
+                blocker: false,
+            }),
+        );
+        assert_eq!(game.legal_moves(), vec![Direction::Right, Direction::Down]);
+        assert!(!game.is_legal_move(Direction::Up));
+        assert!(game.preview(Direction::Left).is_none());
+
+        let preview = game.preview(Direction::Right).unwrap();
+        assert_eq!(preview.get_tile(0, 3).unwrap().value(), 2);
+        assert_eq!(preview.tiles.iter().flatten().count(), 1);
+        assert!(preview.rng == game.rng);
+        assert_eq!(
+            game.legal_moves_codes(),
+            vec![Direction::Right.code(), Direction::Down.code()]
+        );
+        assert_eq!(
+            game.preview_layout(Direction::Right),
+            Some(preview.layout())
+        );
+        assert_eq!(game.preview_layout(Direction::Left), None);
+
+        let moved = game.make_move(Direction::Right).unwrap();
+        assert_eq!(moved.get_tile(0, 3), preview.get_tile(0, 3));
+        assert_eq!(moved.tiles.iter().flatten().count(), 2);
+    }
+
+    #[test]
+    fn evaluate_test() {
+        let empty = row_game(Topology::Classic, [0, 0, 0, 0]);
+        let ordered = row_game(Topology::Classic, [4, 3, 2, 1]);
+        let unordered = row_game(Topology::Classic, [2, 4, 1, 3]);
+        assert!(empty.evaluate() > ordered.evaluate());
+        assert!(ordered.evaluate() > unordered.evaluate());
+
+        let game = row_game(Topology::Classic, [1, 1, 0, 0]);
+        let best = game.best_move().unwrap();
+        assert!(best == Direction::Left || best == Direction::Right);
+
+        let mut g = Game::new_from_seed(4, 3, "");
+        while let Some(d) = g.best_move() {
+            g = g.make_move(d).unwrap();
+        }
+        assert!(g.get_game_over());
+        assert!(g.get_score() > 1000);
+
+        assert_eq!("Up".parse::<Direction>(), Ok(Direction::Up));
+        assert_eq!(Direction::Down.to_string(), "Down");
+    }
+
+    #[test]
+    fn seed_commitment_test() {
+        assert_eq!(
+            seed_commitment("x", 42, "n"),
+            "3ea9d5cde34cac5deae69cfc70a7cf0f440ae4315c1fa36eea8cb9cbf6157b60"
+        );
+        assert_ne!(seed_commitment("x", 43, "n"), seed_commitment("x", 42, "n"));
+        assert_ne!(seed_commitment("x", 42, "m"), seed_commitment("x", 42, "n"));
+    }
+
+    #[test]
+    fn spawn_signature_test() {
+        let spawns = [Spawn::new(3, 1), Spawn::new(12, 2)];
+        let signature = spawn_signature(42, "g", "p", 5, &spawns);
+        assert_eq!(signature.len(), 64);
+        assert_eq!(signature, spawn_signature(42, "g", "p", 5, &spawns));
+        assert_ne!(signature, spawn_signature(43, "g", "p", 5, &spawns));
+        assert_ne!(signature, spawn_signature(42, "g", "q", 5, &spawns));
+        assert_ne!(signature, spawn_signature(42, "g", "p", 6, &spawns));
+        assert_ne!(signature, spawn_signature(42, "g", "p", 5, &spawns[..1]));
+        assert_eq!(
+            signature,
+            digest::to_hex(&digest::hmac_sha256(b"42", b"g:p:5:3,1;12,2"))
+        );
+    }
+
+    #[test]
+    fn player_token_test() {
+        assert_eq!(
+            player_token("secret", "p", 1700000000),
+            "1700000000.06d685d94028bc4fb8718eb5d75be97f2b7a3c6ac7f627cd9863dfc6919622c0"
+        );
+        assert_ne!(
+            player_token("other", "p", 1700000000),
+            player_token("secret", "p", 1700000000)
+        );
+    }
+
+    #[test]
+    fn external_spawn_test() {
+        let server = Game::new_from_definition(4, 11, "x", Topology::Classic, vec![5]).unwrap();
+        let mut client = Game::new_unseeded(4, "x", Topology::Classic, vec![5]).unwrap();
+        for (index, exponent) in server.layout().iter().enumerate() {
+            if *exponent != 0 && *exponent != u8::MAX {
+                client = client
+                    .place_spawn(Spawn::new(index as u32, *exponent))
+                    .unwrap();
+            }
+        }
+        assert!(client.place_spawn(Spawn::new(5, 1)).is_none());
+        assert!(client.place_spawn(Spawn::new(16, 1)).is_none());
+
+        let mut server = server;
+        for d in Direction::ALL.iter().cycle().take(40) {
+            let result = server.play(d.clone());
+            if let Some(game) = result.game() {
+                client = client
+                    .make_move_with_spawn(d.clone(), result.spawn().unwrap())
+                    .unwrap();
+                server = game;
+            }
+        }
+        assert_eq!(client.layout(), server.layout());
+        assert_eq!(client.get_score(), server.get_score());
+        assert_eq!(client.tile_ids(), server.tile_ids());
+
+        let spawns = server.spawns();
+        assert_eq!(spawns.len(), server.moves.len() + 2);
+        let mut rebuilt = Game::new_unseeded(4, "x", Topology::Classic, vec![5]).unwrap();
+        for spawn in spawns[..2].iter() {
+            rebuilt = rebuilt.place_spawn(*spawn).unwrap();
+        }
+        for (d, spawn) in server.moves.iter().zip(spawns[2..].iter()) {
+            rebuilt = rebuilt.make_move_with_spawn(d.clone(), *spawn).unwrap();
+        }
+        assert_eq!(rebuilt.tile_ids(), server.tile_ids());
+        assert_eq!(rebuilt.get_score(), server.get_score());
+    }
+
+    #[test]
+    fn typed_array_test() {
+        let game = row_game(Topology::Classic, [1, 1, 0, 2]);
+        assert_eq!(game.tile_ids()[..4], [0, 1, NO_TILE, 3]);
+        assert!(game.tile_ids()[4..].iter().all(|id| *id == NO_TILE));
+
+        let result = game.play(Direction::Right);
+        let moved = result.game().unwrap();
+        assert_eq!(result.score_gained, 4);
+        assert_eq!(result.merges, 1);
+        assert!(moved.tiles[result.spawned.unwrap() as usize].is_some());
+        assert!(game.tiles[result.spawned.unwrap() as usize].is_none());
+        assert_ne!(moved.merged_ids()[2], NO_TILE);
+        assert_eq!(moved, game.make_move(Direction::Right).unwrap());
+
+        assert!(!game.play(Direction::Up).moved());
+
+        let g = Game::new_from_seed(4, 7, "");
+        let played = g.apply_moves(&[0, 1, 2, 3, 0]);
+        let codes = played.as_ref().map(Game::get_moves);
+        assert_eq!(Some(played.unwrap()), g.apply_moves(&codes.unwrap()));
+        assert!(g.apply_moves(&[4]).is_none());
+    }
+
+    #[test]
+    fn max_tile_test() {
+        assert_eq!(row_game(Topology::Classic, [1, 1, 0, 2]).max_tile(), 4);
+        assert_eq!(row_game(Topology::Classic, [0, 0, 0, 0]).max_tile(), 0);
+
+        let game = Game::new_from_definition(4, 3, "", Topology::Classic, vec![0, 5]).unwrap();
+        assert!(game.max_tile() == 2 || game.max_tile() == 4);
+    }
+
     #[test]
     fn is_ancestor_test() {
         let game1 = Game::new_from_seed(4, 0, "");
@@ -651,6 +1893,57 @@ mod tests {
         assert!(game1 == game2);
     }
 
+    #[test]
+    fn symmetry_test() {
+        let game = Game::new_from_seed(4, 0, "")
+            .make_move(Direction::Down)
+            .unwrap()
+            .make_move(Direction::Left)
+            .unwrap();
+        let layout = game.layout();
+        let mut mirrored = game.clone();
+        for row in 0..4 {
+            for col in 0..4 {
+                mirrored.set_tile(row, col, game.get_tile(row, 3 - col));
+            }
+        }
+
+        assert!(!game.same_layout(&mirrored));
+        assert!(game.same_canonical_layout(&mirrored));
+        assert_eq!(game.canonical_hash(), mirrored.canonical_hash());
+        assert_eq!(
+            game.symmetric_layout(game.canonical_symmetry()),
+            game.canonical_layout()
+        );
+        for s in Symmetry::ALL.iter() {
+            assert!(game.symmetric_layout(*s) >= game.canonical_layout());
+            let mut sorted = s.apply(4, &layout);
+            sorted.sort_unstable();
+            let mut expected = layout.clone();
+            expected.sort_unstable();
+            assert_eq!(sorted, expected);
+        }
+        assert_eq!(
+            Symmetry::Rotate90.apply(4, &Symmetry::Rotate270.apply(4, &layout)),
+            layout
+        );
+    }
+
+    #[test]
+    fn layout_hash_test() {
+        let game1 = Game::new_from_seed(4, 0, "a");
+        let mut game2 = Game::new_from_seed(4, 0, "b");
+        for t in game2.tiles.iter_mut().flatten() {
+            t.id += 10;
+        }
+        assert!(game1 != game2);
+        assert!(game1.same_layout(&game2));
+        assert_eq!(game1.layout_hash(), game2.layout_hash());
+
+        let game3 = game1.make_move(Direction::Down).unwrap();
+        assert!(game1.layout_hash() != game3.layout_hash());
+    }
+
     #[test]
     fn rng_test() {
         assert!(super::rng_test(u64::MAX))
@@ -0,0 +1,200 @@
+//! Move by move comparison of two games on the same seed, from which a client
+//! can race a player against the "ghost" of another.
+
+use crate::Game;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use serde::{Deserialize, Serialize};
+
+/// The number of moves after which each game first had a tile value.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TileRace {
+    pub value: u64,
+    pub first: Option<usize>,
+    pub second: Option<usize>,
+}
+
+/// Two games on the same seed aligned by move index.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GhostRace {
+    /// Score of the first game minus the score of the second after each
+    /// number of moves, up to the moves of the longer game. A game keeps its
+    /// final score after its last move.
+    #[serde(rename = "scoreDiff")]
+    pub score_diff: Vec<i64>,
+    /// Index of the first move which differs between the games, or None if
+    /// one game is a prefix of the other.
+    pub divergence: Option<usize>,
+    /// The tile values reached in either game, from the smallest.
+    pub tiles: Vec<TileRace>,
+}
+
+/// The score after a number of moves, with a bit set for the exponent of
+/// each tile on the board.
+struct Step {
+    score: u64,
+    exponents: u64,
+}
+
+impl Step {
+    fn of(game: &Game) -> Self {
+        Step {
+            score: game.get_score(),
+            exponents: game
+                .layout()
+                .iter()
+                .filter(|e| **e != 0 && **e < 64)
+                .fold(0, |bits, e| bits | 1 << e),
+        }
+    }
+}
+
+/// Replays a game from its initial board, returning each step.
+fn progress(game: &Game) -> Result<Vec<Step>, &'static str> {
+    let mut current = Game::new_from_definition(
+        game.get_size(),
+        game.get_seed(),
+        &game.get_id(),
+        game.get_topology(),
+        game.get_blockers(),
+    )
+    .ok_or("Invalid game definition")?;
+
+    let mut progress = Vec::with_capacity(game.moves.len() + 1);
+    progress.push(Step::of(&current));
+    for d in game.moves.iter() {
+        current = current.make_move(d.clone()).ok_or("Illegal move")?;
+        progress.push(Step::of(&current));
+    }
+    Ok(progress)
+}
+
+/// Returns the number of moves after which each tile value was first on the
+/// board.
+fn first_reached(progress: &[Step]) -> BTreeMap<u64, usize> {
+    let mut reached = BTreeMap::new();
+    for (index, step) in progress.iter().enumerate() {
+        for exponent in 1..64 {
+            if step.exponents & 1 << exponent != 0 {
+                reached.entry(1 << exponent).or_insert(index);
+            }
+        }
+    }
+    reached
+}
+
+impl GhostRace {
+    /// Aligns two games, which must have the same seed, size, topology and
+    /// blockers.
+    pub fn new(first: &Game, second: &Game) -> Result<Self, &'static str> {
+        if first.get_seed() != second.get_seed()
+            || first.get_size() != second.get_size()
+            || first.get_topology() != second.get_topology()
+            || first.get_blockers() != second.get_blockers()
+        {
+            return Err("Games do not share a definition");
+        }
+
+        let first_progress = progress(first)?;
+        let second_progress = progress(second)?;
+
+        let len = first_progress.len().max(second_progress.len());
+        let score_at =
+            |progress: &[Step], index: usize| progress[index.min(progress.len() - 1)].score as i64;
+        let score_diff = (0..len)
+            .map(|i| score_at(&first_progress, i) - score_at(&second_progress, i))
+            .collect();
+
+        let divergence = first
+            .moves
+            .iter()
+            .zip(second.moves.iter())
+            .position(|(a, b)| a != b);
+
+        let first_tiles = first_reached(&first_progress);
+        let second_tiles = first_reached(&second_progress);
+        let mut values: Vec<u64> = first_tiles
+            .keys()
+            .chain(second_tiles.keys())
+            .copied()
+            .collect();
+        values.sort_unstable();
+        values.dedup();
+        let tiles = values
+            .into_iter()
+            .map(|value| TileRace {
+                value,
+                first: first_tiles.get(&value).copied(),
+                second: second_tiles.get(&value).copied(),
+            })
+            .collect();
+
+        Ok(GhostRace {
+            score_diff,
+            divergence,
+            tiles,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::{Direction, Topology};
+
+    fn played_game(directions: &[Direction], count: usize) -> Game {
+        let mut game = Game::new_from_definition(
+            4,
+            42,
+            "76105f46-e609-4d89-bed4-30f8666512c7",
+            Topology::Classic,
+            vec![],
+        )
+        .unwrap();
+        for d in directions.iter().cycle().take(count) {
+            if let Some(g) = game.make_move(d.clone()) {
+                game = g;
+            }
+        }
+        game
+    }
+
+    #[test]
+    fn same_game_test() {
+        let game = played_game(&Direction::ALL, 40);
+        let race = GhostRace::new(&game, &game).unwrap();
+        assert_eq!(race.score_diff.len(), game.moves.len() + 1);
+        assert!(race.score_diff.iter().all(|d| *d == 0));
+        assert_eq!(race.divergence, None);
+        assert!(race
+            .tiles
+            .iter()
+            .all(|t| t.first.is_some() && t.first == t.second));
+        assert_eq!(race.tiles.last().unwrap().value, game.max_tile());
+    }
+
+    #[test]
+    fn diverging_games_test() {
+        let first = played_game(&[Direction::Up, Direction::Left], 30);
+        let second = played_game(&[Direction::Up, Direction::Right], 60);
+        let race = GhostRace::new(&first, &second).unwrap();
+        assert_eq!(race.divergence, Some(1));
+        assert_eq!(
+            race.score_diff.len(),
+            first.moves.len().max(second.moves.len()) + 1
+        );
+        assert_eq!(
+            *race.score_diff.last().unwrap(),
+            first.get_score() as i64 - second.get_score() as i64
+        );
+        assert_eq!(race.tiles[0].value, 2);
+        assert!(race
+            .tiles
+            .iter()
+            .all(|t| t.first.is_some() || t.second.is_some()));
+
+        let other = Game::new_from_seed(4, 43, "other");
+        assert!(GhostRace::new(&first, &other).is_err());
+    }
+}
@@ -0,0 +1,332 @@
+//! Portable binary replay format for complete games, stored in `.t48` files.
+//!
+//! All integers are little endian. A replay consists of:
+//!
+//! | Field      | Size             | Contents                                  |
+//! |------------|------------------|-------------------------------------------|
+//! | magic      | 4                | `T48R`                                    |
+//! | version    | 1                | format version, currently 1               |
+//! | flags      | 1                | bit 0 set if timestamps are present       |
+//! | topology   | 1                | 0 for classic, 1 for wraparound           |
+//! | size       | 2                | board size, at most 16                    |
+//! | seed       | 8                | random seed                               |
+//! | id         | 2 + n            | length and UTF-8 bytes of the game id     |
+//! | blockers   | 2 + 4n           | count and cell indices of the blockers    |
+//! | score      | 8                | final score                               |
+//! | moves      | 4 + ceil(n / 4)  | count and moves packed two bits each      |
+//! | timestamps | 8 + 4(n - 1)     | only if flagged and there are moves: the  |
+//! |            |                  | first move in ms since the Unix epoch,    |
+//! |            |                  | then ms since the previous move           |
+//! | checksum   | 4                | CRC-32 of all preceding bytes             |
+
+use crate::{Direction, Game, Topology};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+const MAGIC: &[u8; 4] = b"T48R";
+const VERSION: u8 = 1;
+const FLAG_TIMESTAMPS: u8 = 1;
+
+/// Largest board size in a replay, which bounds the board allocated while
+/// parsing untrusted data.
+const MAX_SIZE: usize = 16;
+
+/// A complete game with optional move timestamps, which can be converted to
+/// and from the binary replay format.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Replay {
+    game: Game,
+    timestamps: Option<Vec<u64>>,
+}
+
+impl Replay {
+    pub fn new(game: Game) -> Self {
+        Replay {
+            game,
+            timestamps: None,
+        }
+    }
+
+    /// Creates a replay with the time of each move, in milliseconds since the
+    /// Unix epoch. There must be one timestamp per move, in order, and no
+    /// more than about 49 days between consecutive moves.
+    pub fn with_timestamps(game: Game, timestamps: Vec<u64>) -> Result<Self, &'static str> {
+        if timestamps.len() != game.moves.len() {
+            return Err("Timestamp count does not match move count");
+        }
+        if timestamps
+            .windows(2)
+            .any(|w| w[1] < w[0] || w[1] - w[0] > u32::MAX as u64)
+        {
+            return Err("Invalid timestamps");
+        }
+        Ok(Replay {
+            game,
+            timestamps: Some(timestamps),
+        })
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    pub fn into_game(self) -> Game {
+        self.game
+    }
+
+    pub fn timestamps(&self) -> Option<&[u64]> {
+        self.timestamps.as_deref()
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, &'static str> {
+        let g = &self.game;
+        if g.size > MAX_SIZE {
+            return Err("Board too large");
+        }
+        let size = g.size as u16;
+        let id_len = u16::try_from(g.id.len()).map_err(|_| "Game id too long")?;
+        let blockers = g.get_blockers();
+
+        let mut buf = Vec::new();
+        buf.extend_from_slice(MAGIC);
+        buf.push(VERSION);
+        buf.push(if self.timestamps.is_some() {
+            FLAG_TIMESTAMPS
+        } else {
+            0
+        });
+        buf.push(match g.topology {
+            Topology::Classic => 0,
+            Topology::Wraparound => 1,
+        });
+        buf.extend_from_slice(&size.to_le_bytes());
+        buf.extend_from_slice(&g.seed.to_le_bytes());
+        buf.extend_from_slice(&id_len.to_le_bytes());
+        buf.extend_from_slice(g.id.as_bytes());
+        let blocker_count = u16::try_from(blockers.len()).map_err(|_| "Too many blockers")?;
+        buf.extend_from_slice(&blocker_count.to_le_bytes());
+        for b in blockers.iter() {
+            buf.extend_from_slice(&(*b as u32).to_le_bytes());
+        }
+        buf.extend_from_slice(&g.score.to_le_bytes());
+
+        let move_count = u32::try_from(g.moves.len()).map_err(|_| "Too many moves")?;
+        buf.extend_from_slice(&move_count.to_le_bytes());
+        for chunk in g.moves.chunks(4) {
+            let packed = chunk
+                .iter()
+                .enumerate()
+                .fold(0u8, |p, (i, d)| p | d.code() << (2 * i));
+            buf.push(packed);
+        }
+
+        if let Some(timestamps) = &self.timestamps {
+            if let Some(first) = timestamps.first() {
+                buf.extend_from_slice(&first.to_le_bytes());
+                for w in timestamps.windows(2) {
+                    buf.extend_from_slice(&((w[1] - w[0]) as u32).to_le_bytes());
+                }
+            }
+        }
+
+        let checksum = crc32(&buf);
+        buf.extend_from_slice(&checksum.to_le_bytes());
+        Ok(buf)
+    }
+
+    /// Parses a replay and validates it by replaying all moves with the
+    /// engine. Fails if the data is corrupt, has an unsupported version, or
+    /// does not describe a legal game with the stored score.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, &'static str> {
+        if bytes.len() < 4 {
+            return Err("Replay too short");
+        }
+        let (body, checksum) = bytes.split_at(bytes.len() - 4);
+        if crc32(body).to_le_bytes() != checksum {
+            return Err("Invalid checksum");
+        }
+
+        let mut r = Reader { bytes: body };
+        if r.take(4)? != MAGIC {
+            return Err("Not a replay");
+        }
+        if r.u8()? != VERSION {
+            return Err("Unsupported replay version");
+        }
+        let flags = r.u8()?;
+        let topology = match r.u8()? {
+            0 => Topology::Classic,
+            1 => Topology::Wraparound,
+            _ => return Err("Invalid topology"),
+        };
+        let size = r.u16()? as usize;
+        if size > MAX_SIZE {
+            return Err("Board too large");
+        }
+        let seed = r.u64()?;
+        let id_len = r.u16()? as usize;
+        let id = core::str::from_utf8(r.take(id_len)?).map_err(|_| "Invalid game id")?;
+        let blocker_count = r.u16()? as usize;
+        let blockers = (0..blocker_count)
+            .map(|_| r.u32().map(|b| b as usize))
+            .collect::<Result<Vec<_>, _>>()?;
+        let score = r.u64()?;
+        let move_count = r.u32()? as usize;
+        let packed = r.take(move_count.div_ceil(4))?;
+        let moves = (0..move_count)
+            .map(|i| Direction::from_code((packed[i / 4] >> (2 * (i % 4))) & 3).unwrap())
+            .collect::<Vec<_>>();
+
+        let timestamps = if flags & FLAG_TIMESTAMPS != 0 {
+            let mut timestamps = vec![];
+            if move_count > 0 {
+                let mut t = r.u64()?;
+                timestamps.push(t);
+                for _ in 1..move_count {
+                    t = t.checked_add(r.u32()? as u64).ok_or("Invalid timestamps")?;
+                    timestamps.push(t);
+                }
+            }
+            Some(timestamps)
+        } else {
+            None
+        };
+        if !r.bytes.is_empty() {
+            return Err("Trailing data in replay");
+        }
+
+        let mut game = Game::new_from_definition(size, seed, id, topology, blockers)
+            .ok_or("Invalid game definition")?;
+        for d in moves {
+            game = game.make_move(d).ok_or("Invalid move")?;
+        }
+        if game.score != score {
+            return Err("Invalid score");
+        }
+
+        Ok(Replay { game, timestamps })
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], &'static str> {
+        if self.bytes.len() < n {
+            return Err("Replay too short");
+        }
+        let (head, tail) = self.bytes.split_at(n);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, &'static str> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, &'static str> {
+        let mut b = [0; 2];
+        b.copy_from_slice(self.take(2)?);
+        Ok(u16::from_le_bytes(b))
+    }
+
+    fn u32(&mut self) -> Result<u32, &'static str> {
+        let mut b = [0; 4];
+        b.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(b))
+    }
+
+    fn u64(&mut self) -> Result<u64, &'static str> {
+        let mut b = [0; 8];
+        b.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(b))
+    }
+}
+
+/// CRC-32 with the IEEE polynomial, as used by zip and png.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for b in bytes {
+        crc ^= *b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb88320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn played_game() -> Game {
+        let mut game = Game::new_from_definition(
+            4,
+            42,
+            "76105f46-e609-4d89-bed4-30f8666512c7",
+            Topology::Classic,
+            vec![5],
+        )
+        .unwrap();
+        for d in Direction::ALL.iter().cycle().take(30) {
+            if let Some(g) = game.make_move(d.clone()) {
+                game = g;
+            }
+        }
+        game
+    }
+
+    #[test]
+    fn crc32_test() {
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+    }
+
+    #[test]
+    fn round_trip_test() {
+        let game = played_game();
+        let replay = Replay::new(game.clone());
+        let bytes = replay.to_bytes().unwrap();
+        assert_eq!(&bytes[..4], MAGIC);
+        assert_eq!(Replay::from_bytes(&bytes).unwrap(), replay);
+
+        let timestamps = (0..game.moves.len() as u64)
+            .map(|i| 1_650_000_000_000 + i * 750)
+            .collect::<Vec<_>>();
+        let replay = Replay::with_timestamps(game, timestamps).unwrap();
+        let parsed = Replay::from_bytes(&replay.to_bytes().unwrap()).unwrap();
+        assert_eq!(parsed.timestamps(), replay.timestamps());
+        assert!(parsed.game() == replay.game());
+    }
+
+    #[test]
+    fn invalid_replay_test() {
+        let mut bytes = Replay::new(played_game()).to_bytes().unwrap();
+        assert!(Replay::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+
+        bytes[10] ^= 1;
+        assert_eq!(Replay::from_bytes(&bytes), Err("Invalid checksum"));
+
+        assert!(Replay::with_timestamps(played_game(), vec![0]).is_err());
+    }
+
+    #[test]
+    fn board_too_large_test() {
+        let mut bytes = Replay::new(played_game()).to_bytes().unwrap();
+        let body_len = bytes.len() - 4;
+        bytes[7..9].copy_from_slice(&u16::MAX.to_le_bytes());
+        let checksum = crc32(&bytes[..body_len]);
+        bytes[body_len..].copy_from_slice(&checksum.to_le_bytes());
+        assert_eq!(Replay::from_bytes(&bytes), Err("Board too large"));
+
+        let game = Game::new_from_seed(MAX_SIZE + 1, 0, "");
+        assert_eq!(Replay::new(game).to_bytes(), Err("Board too large"));
+    }
+}
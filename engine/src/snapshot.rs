@@ -0,0 +1,154 @@
+//! Snapshots of games, from which more moves can be played without replaying
+//! the moves before them.
+
+use crate::{Game, Tile, Topology};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use oorandom::Rand64;
+use serde::{Deserialize, Serialize};
+
+/// The board, score and random state of a game after `move_count` moves. The
+/// random state is stored as 64 hex digits, since JSON numbers do not hold
+/// 128 bit integers.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// The board as returned by `Game::layout`.
+    pub layout: Vec<u8>,
+    pub score: u64,
+    pub move_count: usize,
+    pub rng: String,
+}
+
+impl Snapshot {
+    /// Restores the game of the snapshot. The tiles are numbered anew and the
+    /// game holds no moves, so moves played on it follow the `move_count`
+    /// moves of the snapshot. Returns None if the snapshot is not valid.
+    pub fn resume(&self, id: &str, seed: u64, topology: Topology) -> Option<Game> {
+        let size = (1..=self.layout.len()).find(|s| s * s >= self.layout.len())?;
+        if size * size != self.layout.len() || self.rng.len() != 64 {
+            return None;
+        }
+        let state = u128::from_str_radix(self.rng.get(..32)?, 16).ok()?;
+        let inc = u128::from_str_radix(self.rng.get(32..)?, 16).ok()?;
+
+        let mut tiles = Vec::with_capacity(self.layout.len());
+        for (id, exponent) in self.layout.iter().enumerate() {
+            tiles.push(match *exponent {
+                0 => None,
+                u8::MAX => Some(Tile::new_blocker(id)),
+                exponent if exponent < 64 => Some(Tile {
+                    id,
+                    exponent,
+                    merged_with: None,
+                    blocker: false,
+                }),
+                _ => return None,
+            });
+        }
+
+        let mut game = Game {
+            id: id.into(),
+            score: self.score,
+            game_over: false,
+            seed,
+            rng: Rand64::from_state((state, inc)),
+            size,
+            topology,
+            next_tile_id: tiles.len(),
+            tiles,
+            moves: Vec::new(),
+        };
+        game.update_game_over();
+        Some(game)
+    }
+
+    /// The snapshot of a game resumed from this snapshot, whose moves follow
+    /// the moves of this snapshot.
+    pub fn after(&self, game: &Game) -> Snapshot {
+        Snapshot {
+            move_count: self.move_count + game.moves.len(),
+            ..game.snapshot()
+        }
+    }
+}
+
+impl Game {
+    /// The snapshot of the game after all its moves.
+    pub fn snapshot(&self) -> Snapshot {
+        let (state, inc) = self.rng.state();
+        Snapshot {
+            layout: self.layout(),
+            score: self.score,
+            move_count: self.moves.len(),
+            rng: format!("{:032x}{:032x}", state, inc),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::Direction;
+    use alloc::vec;
+
+    fn played_game(moves: usize) -> Game {
+        let mut game = Game::new_from_definition(
+            4,
+            42,
+            "76105f46-e609-4d89-bed4-30f8666512c7",
+            Topology::Classic,
+            vec![5],
+        )
+        .unwrap();
+        for d in Direction::ALL.iter().cycle().take(moves) {
+            if let Some(g) = game.make_move(d.clone()) {
+                game = g;
+            }
+        }
+        game
+    }
+
+    #[test]
+    fn resume_test() {
+        let before = played_game(10);
+        let after = played_game(30);
+        let moves = &after.get_moves()[before.moves.len()..];
+
+        let snapshot = before.snapshot();
+        let resumed = snapshot
+            .resume(&before.id, before.seed, before.topology)
+            .unwrap();
+        assert_eq!(resumed.layout(), before.layout());
+        assert_eq!(resumed.get_blockers(), vec![5]);
+
+        let played = resumed.apply_moves(moves).unwrap();
+        assert_eq!(played.layout(), after.layout());
+        assert_eq!(played.score, after.score);
+        assert!(played.rng == after.rng);
+        assert_eq!(snapshot.after(&played), after.snapshot());
+    }
+
+    #[test]
+    fn invalid_snapshot_test() {
+        let game = played_game(10);
+        let resume = |snapshot: Snapshot| snapshot.resume(&game.id, game.seed, game.topology);
+
+        let mut snapshot = game.snapshot();
+        snapshot.layout.pop();
+        assert!(resume(snapshot).is_none());
+
+        let mut snapshot = game.snapshot();
+        snapshot.layout[0] = 64;
+        assert!(resume(snapshot).is_none());
+
+        let mut snapshot = game.snapshot();
+        snapshot.rng.pop();
+        assert!(resume(snapshot).is_none());
+
+        let mut snapshot = game.snapshot();
+        snapshot.rng.replace_range(..1, "x");
+        assert!(resume(snapshot).is_none());
+    }
+}
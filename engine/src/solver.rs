@@ -0,0 +1,203 @@
+//! Bounded beam search over move sequences, for estimating the best score
+//! which can be reached for a seed.
+//!
+//! The spawned tiles only depend on the seed and the moves, so every move
+//! sequence is replayed exactly with `Game::make_move`. After each move the
+//! search keeps the most promising `width` games, ranked by score plus the
+//! `Game::evaluate` heuristic. The result is a lower bound for the best
+//! attainable score, which grows with the width.
+
+use crate::{Direction, Game, Topology};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::Reverse;
+use serde::{Deserialize, Serialize};
+
+pub struct BeamSearch {
+    width: usize,
+    beam: Vec<Game>,
+    best: Game,
+}
+
+/// The state of a beam search, from which it can be resumed. Move sequences
+/// are stored as strings of direction codes, see `Direction::from_code`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub id: String,
+    pub seed: u64,
+    pub size: usize,
+    #[serde(default)]
+    pub topology: Topology,
+    #[serde(default)]
+    pub blockers: Vec<usize>,
+    pub width: usize,
+    pub best: String,
+    pub beam: Vec<String>,
+}
+
+fn encode_moves(game: &Game) -> String {
+    game.moves
+        .iter()
+        .map(|d| (b'0' + d.code()) as char)
+        .collect()
+}
+
+fn decode_moves(moves: &str) -> Option<Vec<u8>> {
+    moves
+        .bytes()
+        .map(|b| b.checked_sub(b'0').filter(|c| *c < 4))
+        .collect()
+}
+
+fn rank(game: &Game) -> i64 {
+    game.score as i64 + game.evaluate()
+}
+
+impl BeamSearch {
+    pub fn new(game: Game, width: usize) -> Self {
+        BeamSearch {
+            width: width.max(1),
+            beam: if game.game_over {
+                vec![]
+            } else {
+                vec![game.clone()]
+            },
+            best: game,
+        }
+    }
+
+    /// The game with the highest score found so far.
+    pub fn best(&self) -> &Game {
+        &self.best
+    }
+
+    /// Number of moves made by the games in the beam.
+    pub fn depth(&self) -> usize {
+        self.beam
+            .first()
+            .map_or(self.best.moves.len(), |g| g.moves.len())
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.beam.is_empty()
+    }
+
+    /// Expands every game in the beam by each legal move and keeps the best
+    /// games. Returns false once no game in the beam can move.
+    pub fn step(&mut self) -> bool {
+        let mut next = self
+            .beam
+            .iter()
+            .flat_map(|g| {
+                Direction::ALL
+                    .iter()
+                    .filter_map(move |d| g.make_move(d.clone()))
+            })
+            .collect::<Vec<_>>();
+
+        for g in next.iter() {
+            if g.score > self.best.score {
+                self.best = g.clone();
+            }
+        }
+
+        // Games with the same board and random state have the same future,
+        // so only the one with the highest score is kept
+        next.sort_by_cached_key(|g| (g.layout(), g.rng.state(), Reverse(g.score)));
+        next.dedup_by(|a, b| a.rng == b.rng && a.layout() == b.layout());
+        next.retain(|g| !g.game_over);
+        next.sort_by_cached_key(|g| Reverse(rank(g)));
+        next.truncate(self.width);
+
+        self.beam = next;
+        !self.beam.is_empty()
+    }
+
+    /// Runs until no game can move or the beam has made `max_moves` moves.
+    pub fn run(&mut self, max_moves: usize) {
+        while self.depth() < max_moves && self.step() {}
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            id: self.best.id.clone(),
+            seed: self.best.seed,
+            size: self.best.size,
+            topology: self.best.topology,
+            blockers: self.best.get_blockers(),
+            width: self.width,
+            best: encode_moves(&self.best),
+            beam: self.beam.iter().map(encode_moves).collect(),
+        }
+    }
+
+    /// Resumes a search by replaying the move sequences of a checkpoint.
+    /// Returns None if the checkpoint does not describe legal games.
+    pub fn from_checkpoint(checkpoint: &Checkpoint) -> Option<Self> {
+        let start = Game::new_from_definition(
+            checkpoint.size,
+            checkpoint.seed,
+            &checkpoint.id,
+            checkpoint.topology,
+            checkpoint.blockers.clone(),
+        )?;
+        let replay = |moves: &str| start.apply_moves(&decode_moves(moves)?);
+        let best = replay(&checkpoint.best)?;
+        let beam = checkpoint
+            .beam
+            .iter()
+            .map(|m| replay(m))
+            .collect::<Option<Vec<_>>>()?;
+        Some(BeamSearch {
+            width: checkpoint.width.max(1),
+            beam,
+            best,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn beam_search_test() {
+        let game = Game::new_from_seed(4, 3, "");
+        let mut search = BeamSearch::new(game.clone(), 8);
+        search.run(200);
+        assert_eq!(search.depth(), 200);
+
+        let best = search.best();
+        assert!(best.score > 1000);
+        let codes = best.moves.iter().map(Direction::code).collect::<Vec<_>>();
+        assert_eq!(game.apply_moves(&codes).as_ref(), Some(best));
+
+        let mut over = game;
+        over.game_over = true;
+        let mut over = BeamSearch::new(over, 8);
+        assert!(over.is_done());
+        assert!(!over.step());
+    }
+
+    #[test]
+    fn checkpoint_test() {
+        let game = Game::new_from_definition(4, 9, "x", Topology::Wraparound, vec![6]).unwrap();
+        let mut search = BeamSearch::new(game.clone(), 4);
+        search.run(50);
+        assert_eq!(search.depth(), 50);
+
+        let checkpoint = search.checkpoint();
+        let mut resumed = BeamSearch::from_checkpoint(&checkpoint).unwrap();
+        assert_eq!(resumed.checkpoint(), checkpoint);
+
+        search.run(100);
+        resumed.run(100);
+        assert!(search.best() == resumed.best());
+
+        let mut invalid = checkpoint;
+        invalid.beam.push("4".into());
+        assert!(BeamSearch::from_checkpoint(&invalid).is_none());
+    }
+}
@@ -0,0 +1,371 @@
+//! Python bindings for the game engine, so that exported games can be
+//! analyzed with the same rules the backend validates them with.
+
+use engine::{Direction, Game, GameExchange, Topology};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::collections::HashMap;
+
+fn value_error<E: ToString>(e: E) -> PyErr {
+    PyValueError::new_err(e.to_string())
+}
+
+fn parse_direction(direction: &str) -> PyResult<Direction> {
+    direction.parse().map_err(value_error)
+}
+
+/// A game state, created from a definition or from exported data.
+#[pyclass(name = "Game")]
+#[derive(Clone)]
+struct PyGame {
+    game: Game,
+}
+
+/// The board after a move, or the initial board for the first frame.
+#[pyclass]
+#[derive(Clone)]
+struct Frame {
+    /// The move leading to this frame, None for the first frame.
+    #[pyo3(get)]
+    direction: Option<String>,
+    #[pyo3(get)]
+    score: u64,
+    /// Points scored by the move leading to this frame.
+    #[pyo3(get)]
+    score_gained: u64,
+    #[pyo3(get)]
+    merges: u32,
+    /// Cell index of the tile spawned after the move.
+    #[pyo3(get)]
+    spawned: Option<u32>,
+    /// Row-major tile exponents, with 0 for empty cells and 255 for blockers.
+    #[pyo3(get)]
+    layout: Vec<u8>,
+}
+
+/// Summary of a complete game.
+#[pyclass]
+#[derive(Clone)]
+struct Stats {
+    #[pyo3(get)]
+    moves: usize,
+    #[pyo3(get)]
+    moves_by_direction: HashMap<String, usize>,
+    #[pyo3(get)]
+    merges: u64,
+    #[pyo3(get)]
+    max_tile: u64,
+    #[pyo3(get)]
+    score: u64,
+    #[pyo3(get)]
+    game_over: bool,
+}
+
+impl PyGame {
+    fn initial(&self) -> Game {
+        Game::new_from_definition(
+            self.game.get_size(),
+            self.game.get_seed(),
+            &self.game.get_id(),
+            self.game.get_topology(),
+            self.game.get_blockers(),
+        )
+        .unwrap()
+    }
+}
+
+#[pymethods]
+impl PyGame {
+    #[new]
+    #[pyo3(signature = (size, seed, id = "", topology = "Classic", blockers = vec![]))]
+    fn new(
+        size: usize,
+        seed: u64,
+        id: &str,
+        topology: &str,
+        blockers: Vec<usize>,
+    ) -> PyResult<Self> {
+        let topology: Topology = topology.parse().map_err(value_error)?;
+        let game = Game::new_from_definition(size, seed, id, topology, blockers)
+            .ok_or_else(|| value_error("Invalid game definition"))?;
+        Ok(PyGame { game })
+    }
+
+    /// Parses and validates a game in the JSON exchange format used by the
+    /// webapp and backend.
+    #[staticmethod]
+    fn from_exchange_json(json: &str) -> PyResult<Self> {
+        let gx = GameExchange::from_json(json.to_owned())
+            .ok_or_else(|| value_error("Invalid game exchange"))?;
+        let game = Game::try_from(&gx).map_err(value_error)?;
+        Ok(PyGame { game })
+    }
+
+    /// Validates a game from the columns of the `games` and `players_games`
+    /// tables, with moves and blockers as stored JSON lists.
+    #[staticmethod]
+    #[pyo3(signature = (seed, size, moves, score, id = "", topology = "Classic", blockers = "[]"))]
+    fn from_row(
+        seed: u64,
+        size: usize,
+        moves: &str,
+        score: u64,
+        id: &str,
+        topology: &str,
+        blockers: &str,
+    ) -> PyResult<Self> {
+        let moves: Vec<Direction> = serde_json::from_str(moves).map_err(value_error)?;
+        let blockers: Vec<usize> = serde_json::from_str(blockers).map_err(value_error)?;
+        let topology: Topology = topology.parse().map_err(value_error)?;
+        let mut game = Game::new_from_definition(size, seed, id, topology, blockers)
+            .ok_or_else(|| value_error("Invalid game definition"))?;
+        for d in moves {
+            game = game
+                .make_move(d)
+                .ok_or_else(|| value_error("Invalid move"))?;
+        }
+        if game.get_score() != score {
+            return Err(value_error("Invalid score"));
+        }
+        Ok(PyGame { game })
+    }
+
+    fn to_exchange_json(&self) -> PyResult<String> {
+        GameExchange::from_game(&self.game)
+            .to_json()
+            .ok_or_else(|| value_error("Could not serialize game"))
+    }
+
+    #[getter]
+    fn id(&self) -> String {
+        self.game.get_id()
+    }
+
+    #[getter]
+    fn seed(&self) -> u64 {
+        self.game.get_seed()
+    }
+
+    #[getter]
+    fn size(&self) -> usize {
+        self.game.get_size()
+    }
+
+    #[getter]
+    fn topology(&self) -> String {
+        self.game.get_topology().to_string()
+    }
+
+    #[getter]
+    fn blockers(&self) -> Vec<usize> {
+        self.game.get_blockers()
+    }
+
+    #[getter]
+    fn score(&self) -> u64 {
+        self.game.get_score()
+    }
+
+    #[getter]
+    fn game_over(&self) -> bool {
+        self.game.get_game_over()
+    }
+
+    #[getter]
+    fn moves(&self) -> Vec<String> {
+        self.game
+            .get_moves()
+            .iter()
+            .filter_map(|c| Direction::from_code(*c))
+            .map(|d| d.to_string())
+            .collect()
+    }
+
+    /// Row-major tile exponents, with 0 for empty cells and 255 for blockers.
+    #[getter]
+    fn layout(&self) -> Vec<u8> {
+        self.game.layout()
+    }
+
+    fn legal_moves(&self) -> Vec<String> {
+        self.game
+            .legal_moves()
+            .iter()
+            .map(|d| d.to_string())
+            .collect()
+    }
+
+    /// Returns the game after the move, or None if the move is not legal.
+    fn make_move(&self, direction: &str) -> PyResult<Option<PyGame>> {
+        let d = parse_direction(direction)?;
+        Ok(self.game.make_move(d).map(|game| PyGame { game }))
+    }
+
+    /// Heuristic value of the board, see `Game::evaluate` in the engine.
+    fn evaluate(&self) -> i64 {
+        self.game.evaluate()
+    }
+
+    /// Heuristic value of the board after sliding in each legal direction.
+    fn evaluate_moves(&self) -> HashMap<String, i64> {
+        self.game
+            .legal_moves()
+            .into_iter()
+            .filter_map(|d| Some((d.to_string(), self.game.preview(d)?.evaluate())))
+            .collect()
+    }
+
+    fn best_move(&self) -> Option<String> {
+        self.game.best_move().map(|d| d.to_string())
+    }
+
+    /// Replays the game from the start, returning the initial board followed
+    /// by the board after each move.
+    fn frames(&self) -> Vec<Frame> {
+        let mut game = self.initial();
+        let mut frames = vec![Frame {
+            direction: None,
+            score: game.get_score(),
+            score_gained: 0,
+            merges: 0,
+            spawned: None,
+            layout: game.layout(),
+        }];
+        for code in self.game.get_moves() {
+            let d = Direction::from_code(code).unwrap();
+            let result = game.play(d.clone());
+            game = result.game().unwrap();
+            frames.push(Frame {
+                direction: Some(d.to_string()),
+                score: game.get_score(),
+                score_gained: result.score_gained,
+                merges: result.merges,
+                spawned: result.spawned,
+                layout: game.layout(),
+            });
+        }
+        frames
+    }
+
+    fn stats(&self) -> Stats {
+        let frames = self.frames();
+        let mut moves_by_direction = Direction::ALL
+            .iter()
+            .map(|d| (d.to_string(), 0))
+            .collect::<HashMap<_, _>>();
+        for d in frames.iter().filter_map(|f| f.direction.clone()) {
+            *moves_by_direction.entry(d).or_default() += 1;
+        }
+        Stats {
+            moves: frames.len() - 1,
+            moves_by_direction,
+            merges: frames.iter().map(|f| f.merges as u64).sum(),
+            max_tile: self.game.max_tile(),
+            score: self.game.get_score(),
+            game_over: self.game.get_game_over(),
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Game(id={:?}, size={}, score={}, moves={})",
+            self.game.get_id(),
+            self.game.get_size(),
+            self.game.get_score(),
+            self.game.get_moves().len()
+        )
+    }
+}
+
+#[pymodule]
+fn twenty48(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyGame>()?;
+    m.add_class::<Frame>()?;
+    m.add_class::<Stats>()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn played_game() -> PyGame {
+        let mut game = PyGame::new(4, 42, "x", "Classic", vec![5]).unwrap();
+        for d in ["Up", "Left", "Down", "Right"].iter().cycle().take(40) {
+            if let Some(g) = game.make_move(d).unwrap() {
+                game = g;
+            }
+        }
+        game
+    }
+
+    #[test]
+    fn frames_test() {
+        let game = played_game();
+        let frames = game.frames();
+        assert_eq!(frames.len(), game.moves().len() + 1);
+        assert_eq!(frames[0].direction, None);
+        assert_eq!(frames[0].score, 0);
+        assert_eq!(frames[0].layout[5], u8::MAX);
+
+        let last = frames.last().unwrap();
+        assert_eq!(last.score, game.score());
+        assert_eq!(last.layout, game.layout());
+        for (frame, d) in frames[1..].iter().zip(game.moves()) {
+            assert_eq!(frame.direction, Some(d));
+            assert!(frame.spawned.is_some());
+        }
+        assert_eq!(
+            frames.iter().map(|f| f.score_gained).sum::<u64>(),
+            game.score()
+        );
+    }
+
+    #[test]
+    fn stats_test() {
+        let game = played_game();
+        let stats = game.stats();
+        assert_eq!(stats.moves, game.moves().len());
+        assert_eq!(stats.moves_by_direction.len(), 4);
+        assert_eq!(
+            stats.moves_by_direction.values().sum::<usize>(),
+            stats.moves
+        );
+        assert_eq!(
+            stats.merges,
+            game.frames().iter().map(|f| f.merges as u64).sum::<u64>()
+        );
+        assert_eq!(stats.score, game.score());
+        assert!(stats.max_tile >= 4);
+    }
+
+    #[test]
+    fn from_row_test() {
+        let game = played_game();
+        let moves = serde_json::to_string(&game.moves()).unwrap();
+        let row = PyGame::from_row(42, 4, &moves, game.score(), "x", "Classic", "[5]").unwrap();
+        assert_eq!(row.layout(), game.layout());
+        assert_eq!(row.blockers(), vec![5]);
+
+        assert!(PyGame::from_row(42, 4, &moves, game.score() + 4, "x", "Classic", "[5]").is_err());
+        assert!(PyGame::from_row(42, 4, &moves, game.score(), "x", "Classic", "[]").is_err());
+        assert!(PyGame::from_row(42, 4, "[\"Sideways\"]", 0, "x", "Classic", "[]").is_err());
+        assert!(PyGame::from_row(42, 4, "[]", 0, "x", "Classic", "[16]").is_err());
+    }
+
+    #[test]
+    fn evaluate_moves_test() {
+        let game = played_game();
+        let evaluations = game.evaluate_moves();
+        let mut directions = evaluations.keys().cloned().collect::<Vec<_>>();
+        let mut legal = game.legal_moves();
+        directions.sort();
+        legal.sort();
+        assert_eq!(directions, legal);
+
+        let best = game.best_move().unwrap();
+        let best_value = evaluations[&best];
+        assert!(evaluations.values().all(|v| *v <= best_value));
+    }
+}
@@ -12,13 +12,91 @@ use wasmbus_rpc::{
 
 pub const SMITHY_VERSION: &str = "1.0";
 
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct AppendPlayerGameMovesRequest {
+    #[serde(rename = "gameId")]
+    #[serde(default)]
+    pub game_id: String,
+    /// Whether the game has no legal moves left, false if not set
+    #[serde(rename = "gameOver")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub game_over: Option<bool>,
+    /// Value of the highest tile, 0 if not set
+    #[serde(rename = "maxTile")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tile: Option<u64>,
+    /// JSON array of the moves to append
+    #[serde(default)]
+    pub moves: String,
+    #[serde(rename = "playerId")]
+    #[serde(default)]
+    pub player_id: String,
+    /// Revision of the stored game the moves are played after
+    #[serde(default)]
+    pub revision: String,
+    /// Score of the game after the appended moves
+    pub score: u64,
+    /// JSON snapshot of the game after the moves, see `engine::Snapshot`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct AppendPlayerGameMovesResponse {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Revision of the stored game
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<String>,
+    #[serde(default)]
+    pub success: bool,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ClaimPlayerRequest {
+    #[serde(rename = "playerId")]
+    #[serde(default)]
+    pub player_id: String,
+    #[serde(default)]
+    pub secret: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ClaimPlayerResponse {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(default)]
+    pub success: bool,
+}
+
 #[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 pub struct CreateGameRequest {
+    /// JSON list of blocker cell indices, none if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blockers: Option<String>,
+    /// Hex encoded SHA-256 of `<game id>:<seed>:<nonce>`, for games whose
+    /// seed is revealed later
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commitment: Option<String>,
+    /// Competitive games get their tiles spawned by the server, which keeps
+    /// the seed secret. False if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub competitive: Option<bool>,
     #[serde(rename = "gameId")]
     #[serde(default)]
     pub game_id: String,
+    /// Random string hashed into the commitment, revealed with the seed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    /// Time when the competition closes and the seed is revealed
+    #[serde(rename = "revealAt")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reveal_at: Option<Timestamp>,
     pub seed: u64,
     pub size: u64,
+    /// Board topology, "Classic" if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub topology: Option<String>,
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
@@ -34,18 +112,32 @@ pub struct CreatePlayerGameRequest {
     #[serde(rename = "gameId")]
     #[serde(default)]
     pub game_id: String,
+    /// Whether the game has no legal moves left, false if not set
+    #[serde(rename = "gameOver")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub game_over: Option<bool>,
+    /// Value of the highest tile, 0 if not set
+    #[serde(rename = "maxTile")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tile: Option<u64>,
     #[serde(default)]
     pub moves: String,
     #[serde(rename = "playerId")]
     #[serde(default)]
     pub player_id: String,
     pub score: u64,
+    /// JSON snapshot of the game after the moves, see `engine::Snapshot`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<String>,
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 pub struct CreatePlayerGameResponse {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub message: Option<String>,
+    /// Revision of the stored game
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<String>,
     #[serde(default)]
     pub success: bool,
 }
@@ -61,6 +153,9 @@ pub struct CreatePlayerRequest {
     #[serde(rename = "playerId")]
     #[serde(default)]
     pub player_id: String,
+    /// Secret issued to the player for signing bearer tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
@@ -82,24 +177,83 @@ pub struct GetGameRequest {
 
 #[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 pub struct GetGameResponse {
+    /// JSON list of blocker cell indices
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blockers: Option<String>,
+    /// Hex encoded SHA-256 of `<game id>:<seed>:<nonce>`, for games whose
+    /// seed is revealed later
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commitment: Option<String>,
+    /// Competitive games get their tiles spawned by the server, which keeps
+    /// the seed secret. False if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub competitive: Option<bool>,
     #[serde(rename = "lastActivity")]
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub last_activity: Option<Timestamp>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub message: Option<String>,
+    /// Random string hashed into the commitment, revealed with the seed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    /// Time when the competition closes and the seed is revealed
+    #[serde(rename = "revealAt")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reveal_at: Option<Timestamp>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub seed: Option<u64>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub size: Option<u64>,
+    /// "Open" until the seed of a competition is revealed, then "Revealed"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
     #[serde(default)]
     pub success: bool,
+    /// Board topology, "Classic" if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub topology: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct GetGlobalLeaderboardRequest {
+    /// Number of entries before and after the requesting player to return
+    /// in aroundMe, none if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub around: Option<u32>,
+    /// Maximum number of entries to return, all if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+    /// "Score" to rank by the best score, "Tile" to rank by the highest tile
+    #[serde(default)]
+    pub metric: String,
+    /// Number of entries to skip, 0 if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u32>,
+    /// "AllTime", "Weekly" or "Daily". Weeks start on Monday, and periods
+    /// are in UTC
+    #[serde(default)]
+    pub period: String,
+    /// Player whose entry is marked and around whom aroundMe is returned
+    #[serde(rename = "playerId")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub player_id: Option<String>,
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 pub struct GetLeaderboardRequest {
+    /// Number of entries before and after the requesting player to return
+    /// in aroundMe, none if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub around: Option<u32>,
     #[serde(rename = "gameId")]
     #[serde(default)]
     pub game_id: String,
+    /// Maximum number of entries to return, all if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+    /// Number of entries to skip, 0 if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u32>,
     #[serde(rename = "playerId")]
     #[serde(default)]
     pub player_id: String,
@@ -107,12 +261,27 @@ pub struct GetLeaderboardRequest {
 
 #[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 pub struct GetLeaderboardResponse {
+    /// Entries around the requesting player, if requested and the player has
+    /// an entry
+    #[serde(rename = "aroundMe")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub around_me: Option<Leaderboard>,
+    /// Offset of the first entry of aroundMe
+    #[serde(rename = "aroundOffset")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub around_offset: Option<u64>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub leaderboard: Option<Leaderboard>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub message: Option<String>,
+    /// Rank of the requesting player, not set if the player has no entry
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rank: Option<u64>,
     #[serde(default)]
     pub success: bool,
+    /// Total number of entries
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
@@ -135,6 +304,9 @@ pub struct GetPlayerGameResponse {
     pub revision: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub score: Option<u64>,
+    /// JSON snapshot of the game after its moves, not set for games stored without one, see `engine::Snapshot`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<String>,
     #[serde(default)]
     pub success: bool,
 }
@@ -176,17 +348,65 @@ pub struct GetPlayerResponse {
     pub last_change: Option<Timestamp>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub message: Option<String>,
+    /// Secret issued to the player for signing bearer tokens, not set for
+    /// players which have not been claimed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
     #[serde(default)]
     pub success: bool,
 }
 
 pub type Leaderboard = Vec<LeaderboardEntry>;
 
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct GetPlayerStatsRequest {
+    #[serde(rename = "playerId")]
+    #[serde(default)]
+    pub player_id: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct GetPlayerStatsResponse {
+    /// Average final score of the games played, 0 if none were played
+    #[serde(rename = "averageScore")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub average_score: Option<f64>,
+    #[serde(rename = "bestScore")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub best_score: Option<u64>,
+    /// Number of games played until no legal moves were left
+    #[serde(rename = "gamesFinished")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub games_finished: Option<u64>,
+    #[serde(rename = "gamesPlayed")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub games_played: Option<u64>,
+    /// Value of the highest tile reached in any game
+    #[serde(rename = "maxTile")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tile: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(default)]
+    pub success: bool,
+    #[serde(rename = "totalMoves")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_moves: Option<u64>,
+}
+
 #[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 pub struct LeaderboardEntry {
     #[serde(rename = "displayName")]
     #[serde(default)]
     pub display_name: String,
+    /// Value of the highest tile
+    #[serde(rename = "maxTile")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tile: Option<u64>,
+    /// Rank of the entry, starting at 1. Entries with equal scores have the
+    /// same rank
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rank: Option<u64>,
     #[serde(rename = "requestingPlayer")]
     #[serde(default)]
     pub requesting_player: bool,
@@ -194,11 +414,62 @@ pub struct LeaderboardEntry {
     pub score: Option<u64>,
 }
 
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct RevealGameRequest {
+    #[serde(rename = "gameId")]
+    #[serde(default)]
+    pub game_id: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct RevealGameResponse {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(default)]
+    pub success: bool,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct TakeRateLimitTokenRequest {
+    #[serde(default)]
+    pub bucket: String,
+    /// Maximum number of tokens in the bucket
+    #[serde(default)]
+    pub capacity: u32,
+    /// Number of tokens added to the bucket per minute
+    #[serde(rename = "refillPerMinute")]
+    #[serde(default)]
+    pub refill_per_minute: u32,
+    /// Route the bucket limits
+    #[serde(default)]
+    pub route: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct TakeRateLimitTokenResponse {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Seconds until the bucket holds a token again, if no token was taken
+    #[serde(rename = "retryAfter")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_after: Option<u32>,
+    #[serde(default)]
+    pub success: bool,
+}
+
 #[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 pub struct UpdatePlayerGameRequest {
     #[serde(rename = "gameId")]
     #[serde(default)]
     pub game_id: String,
+    /// Whether the game has no legal moves left, false if not set
+    #[serde(rename = "gameOver")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub game_over: Option<bool>,
+    /// Value of the highest tile, 0 if not set
+    #[serde(rename = "maxTile")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tile: Option<u64>,
     #[serde(default)]
     pub moves: String,
     #[serde(rename = "playerId")]
@@ -207,12 +478,18 @@ pub struct UpdatePlayerGameRequest {
     #[serde(default)]
     pub revision: String,
     pub score: u64,
+    /// JSON snapshot of the game after the moves, see `engine::Snapshot`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<String>,
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 pub struct UpdatePlayerGameResponse {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub message: Option<String>,
+    /// Revision of the stored game
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<String>,
     #[serde(default)]
     pub success: bool,
 }
@@ -238,6 +515,33 @@ pub struct UpdatePlayerResponse {
     pub success: bool,
 }
 
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct WatchPlayerGameRequest {
+    #[serde(rename = "gameId")]
+    #[serde(default)]
+    pub game_id: String,
+    #[serde(rename = "playerId")]
+    #[serde(default)]
+    pub player_id: String,
+    /// Revision of the game already seen by the watcher
+    #[serde(default)]
+    pub revision: String,
+    /// Longest time to wait for a change, in milliseconds
+    #[serde(rename = "timeoutMs")]
+    pub timeout_ms: u64,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct WatchPlayerGameResponse {
+    /// Whether the revision changed before the timeout
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub changed: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(default)]
+    pub success: bool,
+}
+
 /// wasmbus.contractId: pwatrip:twenty48:state
 /// wasmbus.providerReceive
 /// wasmbus.actorReceive
@@ -294,6 +598,56 @@ pub trait State {
         ctx: &Context,
         arg: &GetLeaderboardRequest,
     ) -> RpcResult<GetLeaderboardResponse>;
+    /// Marks a competition as revealed once its reveal time has passed. Fails if
+    /// the game is not a competition, is already revealed or is still open
+    async fn reveal_game(
+        &self,
+        ctx: &Context,
+        arg: &RevealGameRequest,
+    ) -> RpcResult<RevealGameResponse>;
+    /// Issues a secret to a player which was created without one. Fails if the
+    /// player does not exist or already has a secret
+    async fn claim_player(
+        &self,
+        ctx: &Context,
+        arg: &ClaimPlayerRequest,
+    ) -> RpcResult<ClaimPlayerResponse>;
+    /// Takes a token from a token bucket, which is created full. Fails if the
+    /// bucket holds less than one token. The limits of the route in the
+    /// route_rate_limits table replace the limits of the request
+    async fn take_rate_limit_token(
+        &self,
+        ctx: &Context,
+        arg: &TakeRateLimitTokenRequest,
+    ) -> RpcResult<TakeRateLimitTokenResponse>;
+    /// Leaderboard of the best single game results of each player in a period,
+    /// across all games. Only the current period can be read
+    async fn get_global_leaderboard(
+        &self,
+        ctx: &Context,
+        arg: &GetGlobalLeaderboardRequest,
+    ) -> RpcResult<GetLeaderboardResponse>;
+    /// Lifetime statistics of a player, which are kept when the games they
+    /// were played in expire
+    async fn get_player_stats(
+        &self,
+        ctx: &Context,
+        arg: &GetPlayerStatsRequest,
+    ) -> RpcResult<GetPlayerStatsResponse>;
+    /// Append moves to a stored game, given only the new moves. The database
+    /// still rewrites the whole moves column
+    async fn append_player_game_moves(
+        &self,
+        ctx: &Context,
+        arg: &AppendPlayerGameMovesRequest,
+    ) -> RpcResult<AppendPlayerGameMovesResponse>;
+    /// Wait until the revision of a stored game differs from the given one, or
+    /// until the timeout has passed
+    async fn watch_player_game(
+        &self,
+        ctx: &Context,
+        arg: &WatchPlayerGameRequest,
+    ) -> RpcResult<WatchPlayerGameResponse>;
 }
 
 /// StateReceiver receives messages defined in the State service trait
@@ -402,6 +756,76 @@ pub trait StateReceiver: MessageDispatch + State {
                     arg: Cow::Owned(buf),
                 })
             }
+            "RevealGame" => {
+                let value: RevealGameRequest = deserialize(message.arg.as_ref())
+                    .map_err(|e| RpcError::Deser(format!("message '{}': {}", message.method, e)))?;
+                let resp = State::reveal_game(self, ctx, &value).await?;
+                let buf = serialize(&resp)?;
+                Ok(Message {
+                    method: "State.RevealGame",
+                    arg: Cow::Owned(buf),
+                })
+            }
+            "ClaimPlayer" => {
+                let value: ClaimPlayerRequest = deserialize(message.arg.as_ref())
+                    .map_err(|e| RpcError::Deser(format!("message '{}': {}", message.method, e)))?;
+                let resp = State::claim_player(self, ctx, &value).await?;
+                let buf = serialize(&resp)?;
+                Ok(Message {
+                    method: "State.ClaimPlayer",
+                    arg: Cow::Owned(buf),
+                })
+            }
+            "TakeRateLimitToken" => {
+                let value: TakeRateLimitTokenRequest = deserialize(message.arg.as_ref())
+                    .map_err(|e| RpcError::Deser(format!("message '{}': {}", message.method, e)))?;
+                let resp = State::take_rate_limit_token(self, ctx, &value).await?;
+                let buf = serialize(&resp)?;
+                Ok(Message {
+                    method: "State.TakeRateLimitToken",
+                    arg: Cow::Owned(buf),
+                })
+            }
+            "GetGlobalLeaderboard" => {
+                let value: GetGlobalLeaderboardRequest = deserialize(message.arg.as_ref())
+                    .map_err(|e| RpcError::Deser(format!("message '{}': {}", message.method, e)))?;
+                let resp = State::get_global_leaderboard(self, ctx, &value).await?;
+                let buf = serialize(&resp)?;
+                Ok(Message {
+                    method: "State.GetGlobalLeaderboard",
+                    arg: Cow::Owned(buf),
+                })
+            }
+            "GetPlayerStats" => {
+                let value: GetPlayerStatsRequest = deserialize(message.arg.as_ref())
+                    .map_err(|e| RpcError::Deser(format!("message '{}': {}", message.method, e)))?;
+                let resp = State::get_player_stats(self, ctx, &value).await?;
+                let buf = serialize(&resp)?;
+                Ok(Message {
+                    method: "State.GetPlayerStats",
+                    arg: Cow::Owned(buf),
+                })
+            }
+            "AppendPlayerGameMoves" => {
+                let value: AppendPlayerGameMovesRequest = deserialize(message.arg.as_ref())
+                    .map_err(|e| RpcError::Deser(format!("message '{}': {}", message.method, e)))?;
+                let resp = State::append_player_game_moves(self, ctx, &value).await?;
+                let buf = serialize(&resp)?;
+                Ok(Message {
+                    method: "State.AppendPlayerGameMoves",
+                    arg: Cow::Owned(buf),
+                })
+            }
+            "WatchPlayerGame" => {
+                let value: WatchPlayerGameRequest = deserialize(message.arg.as_ref())
+                    .map_err(|e| RpcError::Deser(format!("message '{}': {}", message.method, e)))?;
+                let resp = State::watch_player_game(self, ctx, &value).await?;
+                let buf = serialize(&resp)?;
+                Ok(Message {
+                    method: "State.WatchPlayerGame",
+                    arg: Cow::Owned(buf),
+                })
+            }
             _ => Err(RpcError::MethodNotHandled(format!(
                 "State::{}",
                 message.method
@@ -689,4 +1113,173 @@ impl<T: Transport + std::marker::Sync + std::marker::Send> State for StateSender
             .map_err(|e| RpcError::Deser(format!("response to {}: {}", "GetLeaderboard", e)))?;
         Ok(value)
     }
+    #[allow(unused)]
+    /// Marks a competition as revealed once its reveal time has passed. Fails if
+    /// the game is not a competition, is already revealed or is still open
+    async fn reveal_game(
+        &self,
+        ctx: &Context,
+        arg: &RevealGameRequest,
+    ) -> RpcResult<RevealGameResponse> {
+        let buf = serialize(arg)?;
+        let resp = self
+            .transport
+            .send(
+                ctx,
+                Message {
+                    method: "State.RevealGame",
+                    arg: Cow::Borrowed(&buf),
+                },
+                None,
+            )
+            .await?;
+        let value = deserialize(&resp)
+            .map_err(|e| RpcError::Deser(format!("response to {}: {}", "RevealGame", e)))?;
+        Ok(value)
+    }
+    #[allow(unused)]
+    /// Issues a secret to a player which was created without one. Fails if the
+    /// player does not exist or already has a secret
+    async fn claim_player(
+        &self,
+        ctx: &Context,
+        arg: &ClaimPlayerRequest,
+    ) -> RpcResult<ClaimPlayerResponse> {
+        let buf = serialize(arg)?;
+        let resp = self
+            .transport
+            .send(
+                ctx,
+                Message {
+                    method: "State.ClaimPlayer",
+                    arg: Cow::Borrowed(&buf),
+                },
+                None,
+            )
+            .await?;
+        let value = deserialize(&resp)
+            .map_err(|e| RpcError::Deser(format!("response to {}: {}", "ClaimPlayer", e)))?;
+        Ok(value)
+    }
+    #[allow(unused)]
+    /// Takes a token from a token bucket, which is created full. Fails if the
+    /// bucket holds less than one token. The limits of the route in the
+    /// route_rate_limits table replace the limits of the request
+    async fn take_rate_limit_token(
+        &self,
+        ctx: &Context,
+        arg: &TakeRateLimitTokenRequest,
+    ) -> RpcResult<TakeRateLimitTokenResponse> {
+        let buf = serialize(arg)?;
+        let resp = self
+            .transport
+            .send(
+                ctx,
+                Message {
+                    method: "State.TakeRateLimitToken",
+                    arg: Cow::Borrowed(&buf),
+                },
+                None,
+            )
+            .await?;
+        let value = deserialize(&resp)
+            .map_err(|e| RpcError::Deser(format!("response to {}: {}", "TakeRateLimitToken", e)))?;
+        Ok(value)
+    }
+    #[allow(unused)]
+    /// Leaderboard of the best single game results of each player in a period,
+    /// across all games. Only the current period can be read
+    async fn get_global_leaderboard(
+        &self,
+        ctx: &Context,
+        arg: &GetGlobalLeaderboardRequest,
+    ) -> RpcResult<GetLeaderboardResponse> {
+        let buf = serialize(arg)?;
+        let resp = self
+            .transport
+            .send(
+                ctx,
+                Message {
+                    method: "State.GetGlobalLeaderboard",
+                    arg: Cow::Borrowed(&buf),
+                },
+                None,
+            )
+            .await?;
+        let value = deserialize(&resp)
+            .map_err(|e| RpcError::Deser(format!("response to {}: {}", "GetGlobalLeaderboard", e)))?;
+        Ok(value)
+    }
+    #[allow(unused)]
+    /// Lifetime statistics of a player, which are kept when the games they
+    /// were played in expire
+    async fn get_player_stats(
+        &self,
+        ctx: &Context,
+        arg: &GetPlayerStatsRequest,
+    ) -> RpcResult<GetPlayerStatsResponse> {
+        let buf = serialize(arg)?;
+        let resp = self
+            .transport
+            .send(
+                ctx,
+                Message {
+                    method: "State.GetPlayerStats",
+                    arg: Cow::Borrowed(&buf),
+                },
+                None,
+            )
+            .await?;
+        let value = deserialize(&resp)
+            .map_err(|e| RpcError::Deser(format!("response to {}: {}", "GetPlayerStats", e)))?;
+        Ok(value)
+    }
+    #[allow(unused)]
+    /// Append moves to a stored game, given only the new moves. The database
+    /// still rewrites the whole moves column
+    async fn append_player_game_moves(
+        &self,
+        ctx: &Context,
+        arg: &AppendPlayerGameMovesRequest,
+    ) -> RpcResult<AppendPlayerGameMovesResponse> {
+        let buf = serialize(arg)?;
+        let resp = self
+            .transport
+            .send(
+                ctx,
+                Message {
+                    method: "State.AppendPlayerGameMoves",
+                    arg: Cow::Borrowed(&buf),
+                },
+                None,
+            )
+            .await?;
+        let value = deserialize(&resp)
+            .map_err(|e| RpcError::Deser(format!("response to {}: {}", "AppendPlayerGameMoves", e)))?;
+        Ok(value)
+    }
+    #[allow(unused)]
+    /// Wait until the revision of a stored game differs from the given one, or
+    /// until the timeout has passed
+    async fn watch_player_game(
+        &self,
+        ctx: &Context,
+        arg: &WatchPlayerGameRequest,
+    ) -> RpcResult<WatchPlayerGameResponse> {
+        let buf = serialize(arg)?;
+        let resp = self
+            .transport
+            .send(
+                ctx,
+                Message {
+                    method: "State.WatchPlayerGame",
+                    arg: Cow::Borrowed(&buf),
+                },
+                None,
+            )
+            .await?;
+        let value = deserialize(&resp)
+            .map_err(|e| RpcError::Deser(format!("response to {}: {}", "WatchPlayerGame", e)))?;
+        Ok(value)
+    }
 }
@@ -18,6 +18,10 @@ let touchDownY = null;
 // see https://cupola.gettysburg.edu/cgi/viewcontent.cgi?article=1025&context=csfac
 const HIGHEST_POSSIBLE_TILE_VALUE = 131072;
 
+// Markers in the typed arrays returned by the engine
+const NO_TILE = 0xffffffff;
+const BLOCKER_EXPONENT = 255;
+
 function resetAnimation(elem) {
   elem.style.animation = 'none';
   elem.offsetHeight;
@@ -36,13 +40,18 @@ function syncBoard(game, board) {
   }
 
   const children = [...board.children];
-
-  for (let r = 0; r < 4; r++) {
-    for (let c = 0; c < 4; c++) {
-
-      const tile = game.get_tile(r, c);
-      if (tile != undefined) {
-        const id = `tile-${tile.id}`;
+  const size = game.get_size();
+  const layout = game.layout();
+  const ids = game.tile_ids();
+  const mergedIds = game.merged_ids();
+
+  for (let r = 0; r < size; r++) {
+    for (let c = 0; c < size; c++) {
+
+      const index = r * size + c;
+      if (ids[index] != NO_TILE) {
+        const id = `tile-${ids[index]}`;
+        const blocker = layout[index] == BLOCKER_EXPONENT;
         let div = undefined;
         // Check for existing tile
         for (let i = 0; i < children.length; i++) {
@@ -57,7 +66,7 @@ function syncBoard(game, board) {
           // Existing tile not found - create new
           div = document.createElement("div");
           div.classList.add("tile");
-          div.classList.add("new");
+          div.classList.add(blocker ? "blocker" : "new");
           div.id = id;
           board.appendChild(div);
         }
@@ -65,8 +74,8 @@ function syncBoard(game, board) {
         const left = `${2 + c * 24.5}%`;
         const top = `${2 + r * 24.5}%`;
 
-        if (tile.merged_with != undefined) {
-          const merged_with_id = `tile-${tile.merged_with}`;
+        if (mergedIds[index] != NO_TILE) {
+          const merged_with_id = `tile-${mergedIds[index]}`;
           div.classList.add("merged");
           resetAnimation(div);
           for (let i = 0; i < children.length; i++) {
@@ -82,10 +91,9 @@ function syncBoard(game, board) {
         }
 
         // Set text and position of tile
-        div.innerHTML = `${tile.value}`;
+        div.innerHTML = blocker ? "" : `${2 ** layout[index]}`;
         div.style.left = left;
         div.style.top = top;
-        tile.free();
       }
 
     }
@@ -93,6 +101,41 @@ function syncBoard(game, board) {
 
 }
 
+function clearPreview() {
+  for (const child of [...board.children]) {
+    if (child.classList.contains("ghost")) {
+      board.removeChild(child);
+    }
+  }
+}
+
+// Outlines where the tiles would end up if the board were slid in the
+// direction, before a new tile is spawned.
+function showPreview(direction) {
+  clearPreview();
+  if (state.get_mode() != Modes.PLAY || direction == undefined) {
+    return;
+  }
+  const game = state.get_current_game();
+  const layout = game.preview_layout(direction);
+  if (layout == undefined) {
+    return;
+  }
+  const size = game.get_size();
+  for (let r = 0; r < size; r++) {
+    for (let c = 0; c < size; c++) {
+      const exponent = layout[r * size + c];
+      if (exponent != 0 && exponent != BLOCKER_EXPONENT) {
+        const div = document.createElement("div");
+        div.classList.add("ghost");
+        div.style.left = `${2 + c * 24.5}%`;
+        div.style.top = `${2 + r * 24.5}%`;
+        board.appendChild(div);
+      }
+    }
+  }
+}
+
 function showGameChanger(e) {
   e.stopPropagation();
   state.set_mode(Modes.SWITCH);
@@ -170,35 +213,43 @@ function updateName() {
   });
 }
 
+function addLeaderboardRow(entry) {
+  const row = document.createElement("div");
+  row.classList.add("leaderboard-slot");
+  const name = document.createElement("span");
+  name.classList.add("leaderboard-name");
+  name.innerText = entry ? `${entry.rank}. ${entry.displayName}` : "…";
+  const score = document.createElement("span");
+  score.classList.add("leaderboard-score");
+  score.innerText = entry ? `${entry.score}` : "";
+  row.appendChild(name);
+  row.appendChild(score);
+  leaderboardList.appendChild(row);
+}
+
 function updateLeaderboard() {
   const leaderboard = state.get_leaderboard();
-  let r = 0;
-  for (let i = 0; i < leaderboard.length; i++) {
-    const entry = leaderboard[i];
-    if (entry.requestingPlayer) {
-      r = i + 1;
-      break;
-    }
-  }
-  if (r > 0) {
-    rank.innerHTML = `${r} of ${leaderboard.length}`;
+  if (leaderboard.rank) {
+    rank.innerHTML = `${leaderboard.rank} of ${leaderboard.total}`;
 
     while (leaderboardList.firstChild) {
       leaderboardList.removeChild(leaderboardList.lastChild);
     }
 
-    for (const entry of leaderboard) {
-      const row = document.createElement("div");
-      row.classList.add("leaderboard-slot");
-      const name = document.createElement("span");
-      name.classList.add("leaderboard-name");
-      name.innerText = entry.displayName;
-      const score = document.createElement("span");
-      score.classList.add("leaderboard-score");
-      score.innerText = `${entry.score}`;
-      row.appendChild(name);
-      row.appendChild(score);
-      leaderboardList.appendChild(row);
+    for (const entry of leaderboard.entries) {
+      addLeaderboardRow(entry);
+    }
+
+    // entries around the player which are not on the first page
+    const aroundMe = (leaderboard.aroundMe ?? [])
+      .filter((_, i) => leaderboard.aroundOffset + i >= leaderboard.entries.length);
+    if (aroundMe.length > 0) {
+      if (leaderboard.aroundOffset > leaderboard.entries.length) {
+        addLeaderboardRow(undefined);
+      }
+      for (const entry of aroundMe) {
+        addLeaderboardRow(entry);
+      }
     }
 
   } else {
@@ -256,8 +307,14 @@ function updateOverlays(game, gameOver) {
   }
 }
 
+function isLegalMove(direction) {
+  return state.get_current_game().legal_moves_codes().includes(direction);
+}
+
 function makeMove(direction) {
-  if (state.get_mode() == Modes.PLAY) {
+  clearPreview();
+  // Directions which do not change the board are ignored
+  if (state.get_mode() == Modes.PLAY && isLegalMove(direction)) {
     state.make_move(direction).then((newGame) => {
       if (newGame != undefined) {
         updateUI();
@@ -298,9 +355,7 @@ function handleKeyDown(e) {
   }
 }
 
-function handleBoardClick(e) {
-  e.preventDefault();
-  e.stopPropagation();
+function directionAt(e) {
   const bounds = board.getBoundingClientRect();
   const x = e.clientX - bounds.left;
   const y = e.clientY - bounds.top;
@@ -317,7 +372,17 @@ function handleBoardClick(e) {
   } else {
     direction = Direction.Down;
   }
-  makeMove(direction);
+  return direction;
+}
+
+function handleBoardClick(e) {
+  e.preventDefault();
+  e.stopPropagation();
+  makeMove(directionAt(e));
+}
+
+function handleBoardHover(e) {
+  showPreview(directionAt(e));
 }
 
 function updateUI() {
@@ -380,33 +445,42 @@ function touchMove(e) {
   const dX = touchDownX - e.touches[0].clientX;
   const dY = touchDownY - e.touches[0].clientY;
 
-  if (Math.sqrt(dX * dX + dY * dY) > board.offsetWidth / 4) {
-
-    let direction = undefined;
+  let direction = undefined;
 
-    if (Math.abs(dX) > Math.abs(dY)) {
-      if (dX > 0) {
-        direction = Direction.Left;
-      } else {
-        direction = Direction.Right;
-      }
+  if (Math.abs(dX) > Math.abs(dY)) {
+    if (dX > 0) {
+      direction = Direction.Left;
     } else {
-      if (dY > 0) {
-        direction = Direction.Up;
-      } else {
-        direction = Direction.Down;
-      }
+      direction = Direction.Right;
     }
+  } else {
+    if (dY > 0) {
+      direction = Direction.Up;
+    } else {
+      direction = Direction.Down;
+    }
+  }
+
+  if (Math.sqrt(dX * dX + dY * dY) > board.offsetWidth / 4) {
 
     makeMove(direction);
 
     touchDownX = null;
     touchDownY = null;
+  } else {
+    // Show where the swipe would slide the tiles before it completes
+    showPreview(direction);
   }
 
   e.preventDefault();
 };
 
+function touchEnd() {
+  touchDownX = null;
+  touchDownY = null;
+  clearPreview();
+}
+
 function stopPropagation(e) {
   e.stopPropagation();
 }
@@ -431,10 +505,13 @@ load().then(() => {
   name.addEventListener("input", handleNameChange);
 
   board.addEventListener("click", handleBoardClick);
+  board.addEventListener("mousemove", handleBoardHover);
+  board.addEventListener("mouseleave", clearPreview);
   document.addEventListener("keydown", handleKeyDown);
 
   board.addEventListener("touchstart", touchStart, false);
   board.addEventListener("touchmove", touchMove, false);
+  board.addEventListener("touchend", touchEnd, false);
 
   newGame.addEventListener("click", showGameChanger);
 
@@ -1,6 +1,6 @@
 import Dexie from "dexie";
 import { v4 as uuidv4 } from 'uuid';
-import { Game, GameExchange } from '../../engine/pkg/engine';
+import { Direction, Game, Topology, player_token } from '../../engine/pkg/engine';
 
 const SyncState = {
     NEW: 1,
@@ -17,9 +17,20 @@ const Modes = {
 
 const STATUS_CODES = {
     OK: 200,
+    CREATED: 201,
     NOT_FOUND: 404,
 };
 
+// The leaderboard shows the top entries and the entries around the player
+const LEADERBOARD_LIMIT = 10;
+const LEADERBOARD_AROUND = 3;
+
+// Games pushed per sync request, the most the backend accepts
+const SYNC_BATCH_SIZE = 50;
+
+// Lifetime of the bearer tokens signed for writes
+const TOKEN_LIFETIME_SECS = 60 * 60;
+
 const BASE_URL = window.location.origin; 
 const API_URL = `${BASE_URL}/api/`;
 
@@ -36,28 +47,48 @@ if (typeof document.hidden !== "undefined") { // Opera 12.10 and Firefox 18 and
 }
 
 function get_gx_js_object(game) {
-    const gx = GameExchange.from_game(game);
-    const gxjs = JSON.parse(gx.to_json());
-    gx.free();
+    const gxjs = {
+        player: "",
+        id: game.get_id(),
+        score: Number(game.get_score()),
+        seed: game.get_seed().toString(),
+        size: game.get_size(),
+        topology: Topology[game.get_topology()],
+        moves: Array.from(game.get_moves(), (code) => Direction[code]),
+    };
+    const blockers = game.get_blockers();
+    if (blockers.length > 0) {
+        gxjs.blockers = Array.from(blockers);
+    }
     return gxjs;
 }
 
-function gxjson_to_game(gxjson) {
-    const gx = GameExchange.from_json(gxjson);
-    if (gx != undefined) {
-        const game = Game.from_exchange(gx);
-        gx.free();
-        if (game != undefined) {
-            return game;
-        }
+function write_headers(playerEntry) {
+    const headers = {
+        'Content-Type': 'application/json'
+    };
+    // The secret is issued by the backend when the player is first stored
+    if (playerEntry.secret) {
+        const expires = BigInt(Math.floor(Date.now() / 1000) + TOKEN_LIFETIME_SECS);
+        headers['Authorization'] = 'Bearer ' + player_token(playerEntry.secret, playerEntry.id, expires);
     }
-    return undefined;
+    return headers;
 }
 
 function gxjs_to_game(gxjs) {
-    if (gxjs != undefined) {
-        const gxjson = JSON.stringify(gxjs);
-        return gxjson_to_game(gxjson);
+    // Competitive games are sent without their seed until it is revealed,
+    // and cannot be rebuilt locally
+    if (gxjs != undefined && gxjs.seed != undefined) {
+        const topology = Topology[gxjs.topology] ?? Topology.Classic;
+        const blockers = Uint32Array.from(gxjs.blockers ?? []);
+        const moves = Uint8Array.from(gxjs.moves, (move) => Direction[move]);
+        const game = Game.from_moves(gxjs.size, BigInt(gxjs.seed), gxjs.id, topology, blockers, moves);
+        if (game != undefined) {
+            if (Number(game.get_score()) == gxjs.score) {
+                return game;
+            }
+            game.free();
+        }
     }
     return undefined;
 }
@@ -74,6 +105,7 @@ class State {
         this.mode = Modes.PLAY;
         this.playerObservers = [];
         this.leaderboardObservers = [];
+        this.leaderboard = { entries: [], total: 0 };
         this.gameObservers = [];
     }
 
@@ -94,7 +126,6 @@ class State {
         } else {
             this.current_game = game;
         }
-        this.leaderboard = [];
         this.start_sync();
         setInterval(() => { this.refreshLeaderboard() }, 5000);
     }
@@ -159,21 +190,34 @@ class State {
             }
         })();
 
+        if (remotePlayerState == STATUS_CODES.OK && !playerEntry.secret) {
+            // players stored before secrets existed are claimed explicitly
+            const response = await fetch(API_URL + 'players/' + playerEntry.id + '/claim', {
+                method: 'POST'
+            });
+            if (response.status == STATUS_CODES.OK) {
+                playerEntry.secret = (await response.json()).secret;
+                await this.db.player.update(1, { secret: playerEntry.secret });
+            }
+        }
+
         if ((remotePlayerState == STATUS_CODES.OK && Date.parse(remotePlayerEntry.lastChange) <= new Date(playerEntry.lastChange))
             || remotePlayerState == STATUS_CODES.NOT_FOUND) {
+            // storing a new player issues its secret
             const body = JSON.stringify({
                 displayName: playerEntry.name,
                 lastChange: (new Date(playerEntry.lastChange)).toISOString(),
             });
             const response = await fetch(API_URL + 'players/' + playerEntry.id, {
                 method: 'PUT',
-                headers: {
-                    'Content-Type': 'application/json'
-                },
+                headers: write_headers(playerEntry),
                 body
             });
-            if (response.status == STATUS_CODES.OK) {
+            if (response.status == STATUS_CODES.OK || response.status == STATUS_CODES.CREATED) {
                 const resp = await response.json();
+                if (resp.secret) {
+                    await this.db.player.update(1, { secret: resp.secret });
+                }
 
                 await this.update_player_from_remote(resp.displayName, Date.parse(resp.lastChange));
             }
@@ -185,19 +229,27 @@ class State {
 
     async push_new_games() {
         const playerEntry = await this.get_player();
-        const gxjss = await this.db.games.where("syncState").equals(SyncState.NEW).toArray();
-        for (const gxjs of gxjss) {
-            const body = JSON.stringify(gxjs);
-            const response = await fetch(API_URL + 'players/' + playerEntry.id + "/games/" + gxjs.id, {
-                method: 'PUT',
-                headers: {
-                    'Content-Type': 'application/json'
-                },
+        const gxjss = await this.db.games.where("syncState").anyOf(SyncState.NEW, SyncState.DIRTY).toArray();
+        // games queued while offline are pushed in batches rather than one
+        // request per game
+        for (let start = 0; start < gxjss.length; start += SYNC_BATCH_SIZE) {
+            const body = JSON.stringify({ games: gxjss.slice(start, start + SYNC_BATCH_SIZE) });
+            const response = await fetch(API_URL + 'players/' + playerEntry.id + "/sync", {
+                method: 'POST',
+                headers: write_headers(playerEntry),
                 body
             });
-            if (response.status == STATUS_CODES.OK) {
-                // game successfully pushed, update DB status to clean
-                await this.db.games.update(gxjs.id, { syncState: SyncState.CLEAN });
+            if (response.status != STATUS_CODES.OK) {
+                return;
+            }
+            const batch = await response.json();
+            for (const result of batch.results) {
+                if (result.status == 'Accepted') {
+                    // game successfully pushed, update DB status to clean
+                    await this.db.games.update(result.id, { syncState: SyncState.CLEAN });
+                } else if (result.status == 'Conflict') {
+                    await this.replaceGameWithRemote(result.game);
+                }
             }
         }
     }
@@ -230,7 +282,10 @@ class State {
             for (newId of newIds) {
                 const response = await fetch(API_URL + 'players/' + playerEntry.id + "/games/" + newId);
                 if (response.status == STATUS_CODES.OK) {
-                    const gxjs = response.json();
+                    const gxjs = await response.json();
+                    if (gxjs.seed == undefined) {
+                        continue;
+                    }
                     gxjs.syncState = SyncState.CLEAN;
                     await this.db.games.put(gxjs);
                 }
@@ -254,6 +309,7 @@ class State {
         for (const localGxjs of localGxjss) {
             const response = await fetch(API_URL + 'players/' + playerEntry.id + "/games/" + localGxjs.id);
             if (response.status == STATUS_CODES.OK) {
+                const etag = response.headers.get('ETag');
                 const remoteGxjs = await response.json();
                 const remoteGame = gxjs_to_game(remoteGxjs);
                 if (remoteGame == undefined) {
@@ -269,12 +325,15 @@ class State {
                     remoteGame.free();
                     localGame.free();
                     if (remoteGxjs.moves.length < localGxjs.moves.length) {
-                        const body = JSON.stringify(localGxjs);
+                        // only the moves played since the fetched revision are
+                        // sent, the next refresh resolves concurrent updates
+                        const body = JSON.stringify({
+                            revision: JSON.parse(etag),
+                            moves: localGxjs.moves.slice(remoteGxjs.moves.length),
+                        });
                         const response = await fetch(API_URL + 'players/' + playerEntry.id + "/games/" + localGxjs.id, {
-                            method: 'PUT',
-                            headers: {
-                                'Content-Type': 'application/json'
-                            },
+                            method: 'PATCH',
+                            headers: write_headers(playerEntry),
                             body
                         });
                         if (response.status == STATUS_CODES.OK) {
@@ -294,7 +353,8 @@ class State {
     async fetchLeaderboard() {
         const playerEntry = await this.get_player();
         const gameId = this.current_game.get_id();
-        const response = await fetch(API_URL + 'players/' + playerEntry.id + '/games/' + gameId + '/leaderboard');
+        const query = `?limit=${LEADERBOARD_LIMIT}&around=${LEADERBOARD_AROUND}`;
+        const response = await fetch(API_URL + 'players/' + playerEntry.id + '/games/' + gameId + '/leaderboard' + query);
         if (response.status == STATUS_CODES.OK) {
             this.leaderboard  = await response.json();
             this.notify_leaderboard_observers();
@@ -315,7 +375,14 @@ class State {
         const response = await fetch(API_URL + 'games/' + gameId);
         if (response.status == STATUS_CODES.OK) {
             const gameParams  = await response.json();
-            const game = Game.new_from_seed(gameParams.size, gameParams.seed, gameId);
+            // Competitive games are played on the server, which keeps the
+            // seed secret
+            if (gameParams.seed == undefined) {
+                return undefined;
+            }
+            const topology = Topology[gameParams.topology] ?? Topology.Classic;
+            const blockers = Uint32Array.from(gameParams.blockers ?? []);
+            const game = Game.new_from_definition(gameParams.size, gameParams.seed, gameId, topology, blockers);
             if (game != undefined) {
                 await this.store_game(game);
             }
@@ -443,7 +510,9 @@ class State {
     }
 
     async make_move(direction) {
-        const newGame = this.current_game.make_move(direction);
+        const result = this.current_game.play(direction);
+        const newGame = result.game();
+        result.free();
         if (newGame != undefined) {
             this.current_game.free();
             this.current_game = newGame;